@@ -23,7 +23,7 @@ fn runtime(c: &mut Criterion) {
 
     c.bench_function("runtime", |b| {
         b.iter(|| {
-            let _state = vec![
+            let _state = [
                 k0.clone(),
                 k0.clone(),
                 k0.clone(),
@@ -40,6 +40,33 @@ fn runtime(c: &mut Criterion) {
     });
 }
 
-// criterion_group!(benches, compiletime_nocopy, compiletime, runtime);
-criterion_group!(benches, compiletime_nocopy);
+fn runtime_into(c: &mut Criterion) {
+    let k0: Tensor = black_box(xdirac!(|0>));
+    let factors = [&k0; 10];
+    let mut state = factors.prod();
+
+    c.bench_function("runtime-into", |b| {
+        b.iter(|| {
+            factors.prod_into(black_box(&mut state));
+        })
+    });
+}
+
+fn operator_product(c: &mut Criterion) {
+    let (h, k0): (Tensor, Tensor) = (xdirac!(H), xdirac!(|0>));
+    let hadamard = black_box([&h; 8].prod());
+    let state = black_box([&k0; 8].prod());
+
+    c.bench_function("operator-product", |b| b.iter(|| &hadamard * &hadamard));
+    c.bench_function("operator-on-ket", |b| b.iter(|| &hadamard * &state));
+}
+
+criterion_group!(
+    benches,
+    compiletime_nocopy,
+    compiletime,
+    runtime,
+    runtime_into,
+    operator_product
+);
 criterion_main!(benches);
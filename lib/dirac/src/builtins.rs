@@ -0,0 +1,341 @@
+use crate::tensor::Tensor;
+use num::complex::Complex64;
+
+// Tolerance on the norm of kets and the trace of density operators passed to
+// quantum information functions
+const TOLERANCE: f64 = 1e-9;
+
+/// Functions that can be called from Dirac notation as `name(args)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Function {
+    Sqrt,
+    Exp,
+    Sin,
+    Cos,
+    Ln,
+    Abs,
+    Conj,
+    Re,
+    Im,
+
+    // Linear algebra
+    Tr,
+    Det,
+    Inv,
+    Rank,
+    Normalize,
+
+    // Quantum information functions, where subsystems are qubits
+    Ptrace,
+    Pt,
+    Entropy,
+    Renyi,
+    Concurrence,
+    Negativity,
+    MutualInfo,
+    Fidelity,
+    TraceDistance,
+}
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "sqrt" => Some(Self::Sqrt),
+            "exp" => Some(Self::Exp),
+            "sin" => Some(Self::Sin),
+            "cos" => Some(Self::Cos),
+            "ln" => Some(Self::Ln),
+            "abs" => Some(Self::Abs),
+            "conj" => Some(Self::Conj),
+            "re" => Some(Self::Re),
+            "im" => Some(Self::Im),
+            "tr" => Some(Self::Tr),
+            "det" => Some(Self::Det),
+            "inv" => Some(Self::Inv),
+            "rank" => Some(Self::Rank),
+            "normalize" => Some(Self::Normalize),
+            "ptrace" => Some(Self::Ptrace),
+            "pt" => Some(Self::Pt),
+            "entropy" => Some(Self::Entropy),
+            "renyi" => Some(Self::Renyi),
+            "concurrence" => Some(Self::Concurrence),
+            "negativity" => Some(Self::Negativity),
+            "mutual_info" => Some(Self::MutualInfo),
+            "fidelity" => Some(Self::Fidelity),
+            "trace_distance" => Some(Self::TraceDistance),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Sqrt => "sqrt",
+            Self::Exp => "exp",
+            Self::Sin => "sin",
+            Self::Cos => "cos",
+            Self::Ln => "ln",
+            Self::Abs => "abs",
+            Self::Conj => "conj",
+            Self::Re => "re",
+            Self::Im => "im",
+            Self::Tr => "tr",
+            Self::Det => "det",
+            Self::Inv => "inv",
+            Self::Rank => "rank",
+            Self::Normalize => "normalize",
+            Self::Ptrace => "ptrace",
+            Self::Pt => "pt",
+            Self::Entropy => "entropy",
+            Self::Renyi => "renyi",
+            Self::Concurrence => "concurrence",
+            Self::Negativity => "negativity",
+            Self::MutualInfo => "mutual_info",
+            Self::Fidelity => "fidelity",
+            Self::TraceDistance => "trace_distance",
+        }
+    }
+
+    // Number of arguments the function must be called with
+    pub fn arity(&self) -> usize {
+        match self {
+            Self::Ptrace
+            | Self::Pt
+            | Self::Renyi
+            | Self::Negativity
+            | Self::MutualInfo
+            | Self::Fidelity
+            | Self::TraceDistance => 2,
+            _ => 1,
+        }
+    }
+
+    // Applies the function, or reports arguments whose values are out of its
+    // domain, such as a singular matrix passed to `inv`
+    pub fn apply(&self, args: &[Tensor]) -> Result<Tensor, String> {
+        assert!(
+            args.len() == self.arity(),
+            "{} expects {} argument(s), got {}",
+            self.name(),
+            self.arity(),
+            args.len()
+        );
+
+        let arg = &args[0];
+
+        // The checker only knows shapes, so the values of states are checked
+        // here
+        let states = match self {
+            Self::Entropy
+            | Self::Renyi
+            | Self::Concurrence
+            | Self::Negativity
+            | Self::MutualInfo => &args[..1],
+            Self::Fidelity | Self::TraceDistance => args,
+            _ => &[],
+        };
+        for state in states {
+            self.check_state(state)?;
+        }
+
+        Ok(match self {
+            // Complex conjugate and real/imaginary parts are taken element-wise
+            Self::Conj => arg.map(|c| c.conj()),
+            Self::Re => arg.map(|c| c.re.into()),
+            Self::Im => arg.map(|c| c.im.into()),
+            Self::Tr => Tensor::new(vec![arg.trace()], (1, 1)),
+            // Zero for singular matrices
+            Self::Det => Tensor::new(vec![arg.det().map_err(|e| format!("det: {}", e))?], (1, 1)),
+            Self::Inv => arg.inv().map_err(|e| format!("cannot invert: {}", e))?,
+            Self::Rank => real(arg.rank() as f64),
+            Self::Normalize if arg.norm() == 0.0 => {
+                return Err("cannot normalize a zero tensor".to_string())
+            }
+            Self::Normalize => arg.unit(),
+            // Traces out qubit k
+            Self::Ptrace => arg.ptrace(&qubits(arg), &[qubit(arg, &args[1])?]),
+            // Transposes qubit k
+            Self::Pt => arg.partial_transpose(&qubits(arg), &[qubit(arg, &args[1])?]),
+            Self::Entropy => real(arg.entropy()),
+            Self::Renyi => real(arg.renyi_entropy(args[1].item().expect("α must be a scalar").re)),
+            Self::Concurrence => real(arg.concurrence()),
+            // Bipartitions into qubit k and the rest of the register
+            Self::Negativity => real(arg.negativity(&qubits(arg), &[qubit(arg, &args[1])?])),
+            Self::MutualInfo => {
+                real(arg.mutual_information(&qubits(arg), &[qubit(arg, &args[1])?]))
+            }
+            Self::Fidelity => real(arg.fidelity(&args[1])),
+            Self::TraceDistance => real(arg.trace_distance(&args[1])),
+            _ => match arg.item() {
+                // Adding 0.0 turns the -0.0 imaginary part left by negating
+                // a real number into 0.0, so that sqrt(-1) and ln(-1) land on
+                // the principal branch.
+                Some(c) => Tensor::new(vec![self.scalar(Complex64::new(c.re, c.im + 0.0))], (1, 1)),
+                None if arg.shape.0 == arg.shape.1 => self.operator(arg)?,
+                None => panic!(
+                    "{} expects a scalar or a square operator, got a tensor of shape {:?}",
+                    self.name(),
+                    arg.shape
+                ),
+            },
+        })
+    }
+
+    // Kets must have unit norm, and density operators must be Hermitian,
+    // positive semidefinite and of unit trace
+    fn check_state(&self, state: &Tensor) -> Result<(), String> {
+        let valid = match state.shape {
+            (_, 1) => (state.norm() - 1.0).abs() <= TOLERANCE,
+            _ => (state.trace() - 1.0).norm() <= TOLERANCE && state.is_positive_semidefinite(),
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(format!(
+                "`{}` expects a normalized ket or a density operator",
+                self.name()
+            )),
+        }
+    }
+
+    fn scalar(&self, c: Complex64) -> Complex64 {
+        match self {
+            Self::Sqrt => c.sqrt(),
+            Self::Exp => c.exp(),
+            Self::Sin => c.sin(),
+            Self::Cos => c.cos(),
+            Self::Ln => c.ln(),
+            Self::Abs => c.norm().into(),
+            _ => unreachable!("{} is not a scalar function", self.name()),
+        }
+    }
+
+    // Matrix functions of square operators, reporting operators whose square
+    // root or logarithm cannot be computed
+    fn operator(&self, a: &Tensor) -> Result<Tensor, String> {
+        let i = Complex64::new(0.0, 1.0);
+
+        Ok(match self {
+            Self::Sqrt => a
+                .sqrtm()
+                .map_err(|e| format!("cannot take the square root: {}", e))?,
+            Self::Exp => a.expm(),
+            Self::Ln => a
+                .logm()
+                .map_err(|e| format!("cannot take the logarithm: {}", e))?,
+            // sin(A) = (exp(iA) - exp(-iA)) / 2i
+            Self::Sin => ((a * i).expm() - (a * -i).expm()) * (-i / 2.0),
+            // cos(A) = (exp(iA) + exp(-iA)) / 2
+            Self::Cos => ((a * i).expm() + (a * -i).expm()) * 0.5,
+            Self::Abs => panic!("abs is not defined for operators, use a norm |A| instead"),
+            _ => unreachable!("{} is not a scalar function", self.name()),
+        })
+    }
+}
+
+fn real(x: f64) -> Tensor {
+    Tensor::new(vec![x.into()], (1, 1))
+}
+
+// Subsystem dimensions of a qubit register state, either a ket or a density
+// operator
+fn qubits(state: &Tensor) -> Vec<usize> {
+    let n = state.shape.0;
+    assert!(
+        n.is_power_of_two(),
+        "a state of dimension {} is not a qubit register",
+        n
+    );
+
+    vec![2; n.trailing_zeros() as usize]
+}
+
+// Qubit index given as a scalar argument
+fn qubit(state: &Tensor, k: &Tensor) -> Result<usize, String> {
+    let n = qubits(state).len();
+
+    match k.item() {
+        Some(k) if k.im == 0.0 && k.re.fract() == 0.0 && k.re >= 0.0 && (k.re as usize) < n => {
+            Ok(k.re as usize)
+        }
+        _ => Err(format!(
+            "qubit index must be an integer between 0 and {}",
+            n - 1
+        )),
+    }
+}
+
+/// Named constants that can be used in Dirac notation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Constant {
+    Pi,
+    E,
+
+    // Single qubit operators
+    I,
+    X,
+    Y,
+    Z,
+    H,
+
+    // Controlled X, with the first qubit as the control
+    Cnot,
+}
+
+impl Constant {
+    pub fn from_name(name: &str) -> Option<Constant> {
+        match name {
+            "pi" | "π" => Some(Self::Pi),
+            "e" => Some(Self::E),
+            "I" => Some(Self::I),
+            "X" => Some(Self::X),
+            "Y" => Some(Self::Y),
+            "Z" => Some(Self::Z),
+            "H" => Some(Self::H),
+            "CNOT" => Some(Self::Cnot),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self) -> Tensor {
+        let c = Complex64::new;
+
+        match self {
+            Self::Pi => Tensor::new(vec![c(std::f64::consts::PI, 0.0)], (1, 1)),
+            Self::E => Tensor::new(vec![c(std::f64::consts::E, 0.0)], (1, 1)),
+            Self::I => Tensor::eye(2),
+            Self::X => Tensor::new(
+                vec![c(0.0, 0.0), c(1.0, 0.0), c(1.0, 0.0), c(0.0, 0.0)],
+                (2, 2),
+            ),
+            Self::Y => Tensor::new(
+                vec![c(0.0, 0.0), c(0.0, -1.0), c(0.0, 1.0), c(0.0, 0.0)],
+                (2, 2),
+            ),
+            Self::Z => Tensor::new(
+                vec![c(1.0, 0.0), c(0.0, 0.0), c(0.0, 0.0), c(-1.0, 0.0)],
+                (2, 2),
+            ),
+            Self::H => {
+                &Tensor::new(
+                    vec![c(1.0, 0.0), c(1.0, 0.0), c(1.0, 0.0), c(-1.0, 0.0)],
+                    (2, 2),
+                ) * std::f64::consts::FRAC_1_SQRT_2
+            }
+            Self::Cnot => {
+                let (o, l) = (c(0.0, 0.0), c(1.0, 0.0));
+
+                #[rustfmt::skip]
+                let data = vec![
+                    l, o, o, o,
+                    o, l, o, o,
+                    o, o, o, l,
+                    o, o, l, o,
+                ];
+
+                Tensor::new(data, (4, 4))
+            }
+        }
+    }
+}
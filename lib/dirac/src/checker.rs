@@ -0,0 +1,382 @@
+//! Static checking of expressions: the shape of every node is inferred from
+//! the shapes of its operands without evaluating anything, so that operands
+//! that do not fit together are reported, with the part of the input they come
+//! from, before any tensor arithmetic can panic on them.
+
+use crate::builtins::{Constant, Function};
+use crate::error::Error;
+use crate::expression::{restore, state, terms, Context, Expression, Index, Kind, Variables};
+use std::fmt::{self, Display};
+
+/// What an expression evaluates to, along with its dimensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Scalar,
+    Ket(usize),
+    Bra(usize),
+    // Rows and columns
+    Operator(usize, usize),
+}
+
+impl Type {
+    fn shape(self) -> (usize, usize) {
+        match self {
+            Self::Scalar => (1, 1),
+            Self::Ket(n) => (n, 1),
+            Self::Bra(n) => (1, n),
+            Self::Operator(m, n) => (m, n),
+        }
+    }
+
+    fn is_square(self) -> bool {
+        let (m, n) = self.shape();
+
+        m == n
+    }
+
+    // Dimension of a state given as a ket or a density operator
+    fn state(self) -> Option<usize> {
+        match self {
+            Self::Ket(n) => Some(n),
+            Self::Operator(m, n) if m == n => Some(n),
+            _ => None,
+        }
+    }
+}
+
+impl From<(usize, usize)> for Type {
+    fn from(shape: (usize, usize)) -> Type {
+        match shape {
+            (1, 1) => Self::Scalar,
+            (n, 1) => Self::Ket(n),
+            (1, n) => Self::Bra(n),
+            (m, n) => Self::Operator(m, n),
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scalar => write!(f, "a scalar"),
+            Self::Ket(n) => write!(f, "a ket of dimension {}", n),
+            Self::Bra(n) => write!(f, "a bra of dimension {}", n),
+            Self::Operator(m, n) => write!(f, "an operator of shape {}x{}", m, n),
+        }
+    }
+}
+
+impl Expression {
+    // Infers the type of the expression, or reports the first node whose
+    // operands do not fit together. Expressions that check are guaranteed
+    // not to fail on shapes when evaluated, although values can still be out
+    // of range, as for the inverse of a singular matrix, which `compute`
+    // reports.
+    pub fn check(&self) -> Result<Type, Error> {
+        let width = self.register_width()?;
+
+        self.check_in(&mut Context::with_width(width))
+    }
+
+    // Number of qubits Pauli strings act on: one past the highest qubit index
+    // used, widened to the narrowest register the rest of the expression
+    // checks against, so that X0 |00> acts on two qubits. When no width fits,
+    // the narrowest one is used for the checker to report the mismatch.
+    pub(crate) fn register_width(&self) -> Result<usize, Error> {
+        let narrowest = match self.width(&mut Variables::new())? {
+            Some(width) => width,
+            None => return Ok(0),
+        };
+        let fits = |width| self.check_in(&mut Context::with_width(width)).is_ok();
+
+        Ok((narrowest..usize::BITS as usize)
+            .find(|&width| fits(width))
+            .unwrap_or(narrowest))
+    }
+
+    fn check_in(&self, context: &mut Context<Type>) -> Result<Type, Error> {
+        let error = |message: String| Err(Error::new(message, self.span));
+        // Dimension of a register, which has to fit in a usize
+        let dimension = |qubits: usize| {
+            u32::try_from(qubits)
+                .ok()
+                .and_then(|qubits| 1usize.checked_shl(qubits))
+                .ok_or_else(|| {
+                    Error::new(format!("register too wide: {} qubits", qubits), self.span)
+                })
+        };
+
+        match &self.kind {
+            Kind::Scalar(_) | Kind::Constant(Constant::Pi) | Kind::Constant(Constant::E) => {
+                Ok(Type::Scalar)
+            }
+            Kind::Constant(constant) => Ok(Type::from(constant.value().shape)),
+            Kind::Bra(bra) => Ok(Type::Bra(dimension(bra.len())?)),
+            Kind::Ket(ket) => Ok(Type::Ket(dimension(ket.len())?)),
+            Kind::NamedBra(name) | Kind::NamedKet(name) => {
+                let (m, n) = state(&context.states, name)
+                    .map_err(|message| Error::new(message, self.span))?
+                    .shape();
+
+                match self.kind {
+                    Kind::NamedBra(_) => Ok(Type::from((n, m))),
+                    _ => Ok(Type::from((m, n))),
+                }
+            }
+            Kind::Outer(ket, bra) => Ok(Type::from((dimension(ket.len())?, dimension(bra.len())?))),
+            Kind::AdditiveInverse(expr) | Kind::Parenthised(expr) => expr.check_in(context),
+            Kind::Dagger(expr) => {
+                let (m, n) = expr.check_in(context)?.shape();
+
+                Ok(Type::from((n, m)))
+            }
+            Kind::Norm(expr) => expr.check_in(context).map(|_| Type::Scalar),
+            Kind::Mul(a, b) => {
+                let (a, b) = (a.check_in(context)?, b.check_in(context)?);
+
+                match product(a, b) {
+                    Some(t) => Ok(t),
+                    None if matches!(
+                        (a, b),
+                        (Type::Ket(_), Type::Ket(_)) | (Type::Bra(_), Type::Bra(_))
+                    ) =>
+                    {
+                        error(format!(
+                            "cannot multiply {} by {}, use x for the Kronecker product",
+                            a, b
+                        ))
+                    }
+                    None => error(format!("cannot multiply {} by {}", a, b)),
+                }
+            }
+            Kind::Div(a, b) => {
+                let (a, b) = (a.check_in(context)?, b.check_in(context)?);
+
+                match (b, b.is_square()) {
+                    (Type::Scalar, _) => Ok(a),
+                    // Right division by a matrix multiplies by its inverse,
+                    // and a scalar over a matrix scales the inverse
+                    (b, true) if a == Type::Scalar => Ok(b),
+                    (b, true) if a.shape().1 == b.shape().0 => Ok(a),
+                    _ => error(format!("cannot divide {} by {}", a, b)),
+                }
+            }
+            Kind::Add(a, b) | Kind::Sub(a, b) => {
+                let (a, b) = (a.check_in(context)?, b.check_in(context)?);
+
+                match (a == b, &self.kind) {
+                    (true, _) => Ok(a),
+                    (false, Kind::Add(..)) => error(format!("cannot add {} to {}", b, a)),
+                    (false, _) => error(format!("cannot subtract {} from {}", b, a)),
+                }
+            }
+            Kind::Kronecker(a, b) => {
+                let ((m, n), (p, q)) = (a.check_in(context)?.shape(), b.check_in(context)?.shape());
+
+                match (m.checked_mul(p), n.checked_mul(q)) {
+                    (Some(m), Some(n)) => Ok(Type::from((m, n))),
+                    _ => error("register too wide for a Kronecker product".to_string()),
+                }
+            }
+            Kind::Pow(base, exponent) => {
+                let t = base.check_in(context)?;
+
+                match exponent.check_in(context)? {
+                    Type::Scalar if t.is_square() => Ok(t),
+                    Type::Scalar => error(format!("cannot raise {} to a power", t)),
+                    e => Err(Error::new(
+                        format!("exponent must be a scalar, got {}", e),
+                        exponent.span,
+                    )),
+                }
+            }
+            Kind::Inner(a, b) => {
+                let (a, b) = (a.check_in(context)?, b.check_in(context)?);
+                let ((m, n), (p, q)) = (a.shape(), b.shape());
+
+                match m * n == p * q {
+                    true => Ok(Type::Scalar),
+                    false => error(format!("cannot take the inner product of {} and {}", a, b)),
+                }
+            }
+            Kind::Commutator(a, b) | Kind::Anticommutator(a, b) => {
+                let (a, b) = (a.check_in(context)?, b.check_in(context)?);
+
+                let name = match self.kind {
+                    Kind::Commutator(..) => "commutator",
+                    _ => "anticommutator",
+                };
+
+                match (product(a, b), product(b, a)) {
+                    (Some(ab), Some(ba)) if ab == ba => Ok(ab),
+                    _ => error(format!("cannot take the {} of {} and {}", name, a, b)),
+                }
+            }
+            Kind::Function(function, args) => {
+                let types = args
+                    .iter()
+                    .map(|arg| arg.check_in(context))
+                    .collect::<Result<Vec<Type>, Error>>()?;
+
+                apply(*function, &types).map_err(|message| Error::new(message, self.span))
+            }
+            Kind::Pauli(_) => {
+                let n = dimension(context.width)?;
+
+                Ok(Type::from((n, n)))
+            }
+            Kind::Sum(variable, from, to, body) => {
+                let value = |index: &Index, context: &Context<Type>| {
+                    index
+                        .try_value(&context.variables)
+                        .map_err(|message| Error::new(message, self.span))
+                };
+                let (from, to) = (value(from, context)?, value(to, context)?);
+                let shadowed = context.variables.get(variable).cloned();
+
+                // Stops at the first term that does not fit the others
+                let sum = terms(variable, from, to, self.span)?.try_fold(
+                    None,
+                    |sum: Option<Type>, value| {
+                        context.variables.insert(variable.clone(), value);
+                        let term = body.check_in(context)?;
+
+                        match sum {
+                            Some(sum) if sum != term => Err(Error::new(
+                                format!("cannot add {} to {}", term, sum),
+                                self.span,
+                            )),
+                            _ => Ok(Some(term)),
+                        }
+                    },
+                );
+
+                restore(&mut context.variables, variable, shadowed);
+
+                match sum? {
+                    Some(sum) => Ok(sum),
+                    None => error(format!(
+                        "empty sum over {} from {} to {}",
+                        variable, from, to
+                    )),
+                }
+            }
+            Kind::Let(variable, value, body) => {
+                let value = value
+                    .try_value(&context.variables)
+                    .map_err(|message| Error::new(message, self.span))?;
+                let shadowed = context.variables.insert(variable.clone(), value);
+                let result = body.check_in(context);
+
+                restore(&mut context.variables, variable, shadowed);
+
+                result
+            }
+            // Only kets can be named, so that ⟨ψ| is always their dagger
+            Kind::LetState(name, value, body) => {
+                let shadowed = match value.check_in(context)? {
+                    Type::Ket(n) => context.states.insert(name.clone(), Type::Ket(n)),
+                    t => {
+                        return Err(Error::new(
+                            format!("`{}` must be bound to a ket, got {}", name, t),
+                            value.span,
+                        ))
+                    }
+                };
+                let result = body.check_in(context);
+
+                restore(&mut context.states, name, shadowed);
+
+                result
+            }
+        }
+    }
+}
+
+// Type of a product, where scalars multiply anything
+fn product(a: Type, b: Type) -> Option<Type> {
+    match (a, b) {
+        (Type::Scalar, t) | (t, Type::Scalar) => Some(t),
+        (a, b) => {
+            let ((m, n), (p, q)) = (a.shape(), b.shape());
+
+            match n == p {
+                true => Some(Type::from((m, q))),
+                false => None,
+            }
+        }
+    }
+}
+
+// Type of a function's result, following Function::apply
+fn apply(function: Function, args: &[Type]) -> Result<Type, String> {
+    let name = function.name();
+    let arg = args[0];
+
+    // Dimension of a state on a register of at least one qubit
+    let register = |t: Type| match t.state() {
+        Some(n) if n.is_power_of_two() && n > 1 => Ok(n),
+        _ => Err(format!(
+            "`{}` expects a qubit register state, got {}",
+            name, t
+        )),
+    };
+    let scalar = |t: Type| match t {
+        Type::Scalar => Ok(()),
+        t => Err(format!("`{}` expects a scalar argument, got {}", name, t)),
+    };
+
+    match function {
+        Function::Conj | Function::Re | Function::Im | Function::Normalize => Ok(arg),
+        Function::Rank => Ok(Type::Scalar),
+        Function::Tr | Function::Det | Function::Inv if !arg.is_square() => {
+            Err(format!("`{}` expects a square operator, got {}", name, arg))
+        }
+        Function::Tr | Function::Det => Ok(Type::Scalar),
+        Function::Inv => Ok(arg),
+        Function::Ptrace => {
+            let n = register(arg)?;
+            scalar(args[1])?;
+
+            Ok(Type::from((n / 2, n / 2)))
+        }
+        Function::Pt => {
+            let n = register(arg)?;
+            scalar(args[1])?;
+
+            Ok(Type::Operator(n, n))
+        }
+        Function::Entropy => register(arg).map(|_| Type::Scalar),
+        Function::Renyi | Function::Negativity | Function::MutualInfo => {
+            register(arg)?;
+            scalar(args[1])?;
+
+            Ok(Type::Scalar)
+        }
+        Function::Concurrence => match register(arg)? {
+            4 => Ok(Type::Scalar),
+            _ => Err(format!("`{}` expects a two qubit state, got {}", name, arg)),
+        },
+        Function::Fidelity | Function::TraceDistance => {
+            match (register(arg)?, register(args[1])?) {
+                (n, m) if n == m => Ok(Type::Scalar),
+                _ => Err(format!(
+                    "`{}` expects states of the same dimension, got {} and {}",
+                    name, arg, args[1]
+                )),
+            }
+        }
+        // Scalar functions, which are matrix functions on square operators
+        _ => match arg {
+            Type::Scalar => Ok(arg),
+            Type::Operator(..) if function == Function::Abs => {
+                Err("abs is not defined for operators, use a norm |A| instead".to_string())
+            }
+            t if t.is_square() => Ok(t),
+            t => Err(format!(
+                "`{}` expects a scalar or a square operator, got {}",
+                name, t
+            )),
+        },
+    }
+}
@@ -0,0 +1,213 @@
+//! Export of gate applications such as `(H x I) CNOT |00>` as OpenQASM 3 and
+//! Quil programs. The basis state is prepared from |0...0>, then the gates are
+//! applied from right to left. As in the OpenQASM front end, qubit 0 is the
+//! leftmost factor of the Kronecker products.
+
+use crate::builtins::Constant;
+use crate::error::Error;
+use crate::expression::{Expression, Kind};
+
+pub trait ToQasm {
+    fn to_qasm(&self) -> Result<String, Error>;
+}
+
+pub trait ToQuil {
+    fn to_quil(&self) -> Result<String, Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Gate {
+    X,
+    Y,
+    Z,
+    H,
+    Sdg,
+    Cnot,
+}
+
+impl Gate {
+    fn qasm(&self) -> &'static str {
+        match self {
+            Self::X => "x",
+            Self::Y => "y",
+            Self::Z => "z",
+            Self::H => "h",
+            Self::Sdg => "sdg",
+            Self::Cnot => "cx",
+        }
+    }
+
+    fn quil(&self) -> &'static str {
+        match self {
+            Self::X => "X",
+            Self::Y => "Y",
+            Self::Z => "Z",
+            Self::H => "H",
+            Self::Sdg => "DAGGER S",
+            Self::Cnot => "CNOT",
+        }
+    }
+}
+
+// The gates of a program on qubits 0..width, in the order they are applied
+struct Program {
+    width: usize,
+    gates: Vec<(Gate, Vec<usize>)>,
+}
+
+impl ToQasm for Expression {
+    fn to_qasm(&self) -> Result<String, Error> {
+        let program = program(self)?;
+        let mut qasm = format!(
+            "OPENQASM 3.0;\ninclude \"stdgates.inc\";\nqubit[{}] q;\n",
+            program.width
+        );
+
+        for (gate, qubits) in program.gates {
+            let operands = qubits
+                .iter()
+                .map(|k| format!("q[{}]", k))
+                .collect::<Vec<String>>();
+
+            qasm += &format!("{} {};\n", gate.qasm(), operands.join(", "));
+        }
+
+        Ok(qasm)
+    }
+}
+
+impl ToQuil for Expression {
+    fn to_quil(&self) -> Result<String, Error> {
+        let program = program(self)?;
+        let mut quil = String::new();
+
+        for (gate, qubits) in program.gates {
+            let operands = qubits.iter().map(usize::to_string).collect::<Vec<String>>();
+
+            quil += &format!("{} {}\n", gate.quil(), operands.join(" "));
+        }
+
+        Ok(quil)
+    }
+}
+
+fn program(expression: &Expression) -> Result<Program, Error> {
+    let mut factors = Vec::new();
+    flatten(expression, product, &mut factors);
+
+    // The rightmost factor is the state, and the gates act on it from right
+    // to left
+    let (state, operators) = factors.split_last().unwrap();
+    let label = basis(state)?;
+    let mut gates = preparation(&label);
+
+    for operator in operators.iter().rev() {
+        let mut terms = Vec::new();
+        flatten(operator, kronecker, &mut terms);
+
+        let mut qubit = 0;
+        for term in terms {
+            let (gate, arity) = match term.kind {
+                Kind::Constant(Constant::I) => (None, 1),
+                Kind::Constant(Constant::X) => (Some(Gate::X), 1),
+                Kind::Constant(Constant::Y) => (Some(Gate::Y), 1),
+                Kind::Constant(Constant::Z) => (Some(Gate::Z), 1),
+                Kind::Constant(Constant::H) => (Some(Gate::H), 1),
+                Kind::Constant(Constant::Cnot) => (Some(Gate::Cnot), 2),
+                _ => {
+                    return Err(Error::new(
+                        "only the gates I, X, Y, Z, H and CNOT can be exported",
+                        term.span,
+                    ))
+                }
+            };
+
+            if let Some(gate) = gate {
+                gates.push((gate, (qubit..qubit + arity).collect()));
+            }
+            qubit += arity;
+        }
+
+        if qubit != label.len() {
+            return Err(Error::new(
+                format!(
+                    "a gate on {} qubit(s) is applied to {} qubit(s)",
+                    qubit,
+                    label.len()
+                ),
+                operator.span,
+            ));
+        }
+    }
+
+    Ok(Program {
+        width: label.len(),
+        gates,
+    })
+}
+
+// The label of a basis state, such as |0+> or |0> x |+>
+fn basis(state: &Expression) -> Result<String, Error> {
+    let mut kets = Vec::new();
+    flatten(state, kronecker, &mut kets);
+
+    kets.iter()
+        .map(|ket| match &ket.kind {
+            Kind::Ket(label) => Ok(label.as_str()),
+            _ => Err(Error::new(
+                "expected gates applied to a basis state, such as `(H x I) CNOT |00>`",
+                state.span,
+            )),
+        })
+        .collect()
+}
+
+// Gates preparing a basis state from |0...0>, where |-> is (|0> - i|1>)/√2
+fn preparation(label: &str) -> Vec<(Gate, Vec<usize>)> {
+    let mut gates = Vec::new();
+
+    for (k, c) in label.chars().enumerate() {
+        match c {
+            '1' => gates.push((Gate::X, vec![k])),
+            '+' => gates.push((Gate::H, vec![k])),
+            '-' => gates.extend([(Gate::H, vec![k]), (Gate::Sdg, vec![k])]),
+            _ => (),
+        }
+    }
+
+    gates
+}
+
+// Collects the operands of nested applications of an associative operation,
+// looking through parentheses
+fn flatten<'a>(
+    expression: &'a Expression,
+    split: fn(&Kind) -> Option<(&Expression, &Expression)>,
+    factors: &mut Vec<&'a Expression>,
+) {
+    if let Kind::Parenthised(inner) = &expression.kind {
+        return flatten(inner, split, factors);
+    }
+
+    match split(&expression.kind) {
+        Some((a, b)) => {
+            flatten(a, split, factors);
+            flatten(b, split, factors);
+        }
+        None => factors.push(expression),
+    }
+}
+
+fn product(kind: &Kind) -> Option<(&Expression, &Expression)> {
+    match kind {
+        Kind::Mul(a, b) => Some((a, b)),
+        _ => None,
+    }
+}
+
+fn kronecker(kind: &Kind) -> Option<(&Expression, &Expression)> {
+    match kind {
+        Kind::Kronecker(a, b) => Some((a, b)),
+        _ => None,
+    }
+}
@@ -0,0 +1,330 @@
+//! LaTeX rendering of expressions, as they would be typeset in a paper, and of
+//! results as matrices: `\frac{1}{\sqrt{2}}(\vert 0\rangle + \vert 1\rangle)`.
+
+use crate::builtins::{Constant, Function};
+use crate::expression::{Expression, Index, Kind};
+use crate::symbolic::{Exact, ExactTensor};
+use num::complex::Complex64;
+use num::rational::BigRational;
+use num::{One, Signed, Zero};
+use tensor::{Pauli, Tensor};
+
+pub trait ToLatex {
+    fn to_latex(&self) -> String;
+}
+
+// How tightly a rendered expression binds. Operands binding less tightly than
+// their operator requires are parenthesised.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Sum,
+    Product,
+    Negation,
+    Fraction,
+    Power,
+    Atom,
+}
+
+impl ToLatex for Expression {
+    fn to_latex(&self) -> String {
+        render(self).0
+    }
+}
+
+// Scalars are written as numbers, and other tensors as matrices
+impl ToLatex for Tensor {
+    fn to_latex(&self) -> String {
+        matrix(self.shape, self.data.iter().map(|c| complex(*c)).collect())
+    }
+}
+
+impl ToLatex for ExactTensor {
+    fn to_latex(&self) -> String {
+        matrix(self.shape, self.data.iter().map(exact).collect())
+    }
+}
+
+fn matrix((m, n): (usize, usize), entries: Vec<String>) -> String {
+    if (m, n) == (1, 1) {
+        return entries[0].clone();
+    }
+
+    let rows = entries
+        .chunks(n)
+        .map(|row| row.join(" & "))
+        .collect::<Vec<String>>();
+
+    format!(
+        "\\begin{{bmatrix}} {} \\end{{bmatrix}}",
+        rows.join(" \\\\ ")
+    )
+}
+
+fn complex(c: Complex64) -> String {
+    let imaginary = |im: f64| match (im == 1.0, im == -1.0) {
+        (true, _) => "i".to_string(),
+        (_, true) => "-i".to_string(),
+        _ => format!("{}i", im),
+    };
+
+    if c.im == 0.0 {
+        c.re.to_string()
+    } else if c.re == 0.0 {
+        imaginary(c.im)
+    } else if c.im < 0.0 {
+        format!("{} - {}", c.re, imaginary(-c.im))
+    } else {
+        format!("{} + {}", c.re, imaginary(c.im))
+    }
+}
+
+// Terms q √r e^{iπθ} are written as \frac{3i\sqrt{2}}{4} or \sqrt{2}e^{i\pi/5}
+fn exact(x: &Exact) -> String {
+    if x.is_zero() {
+        return "0".to_string();
+    }
+
+    let mut latex = String::new();
+
+    for (k, (q, r, theta)) in x.terms().enumerate() {
+        match (k, q.is_negative()) {
+            (0, true) => latex.push('-'),
+            (0, false) => {}
+            (_, true) => latex.push_str(" - "),
+            (_, false) => latex.push_str(" + "),
+        }
+
+        let numerator = q.numer().abs();
+        let mut term = String::new();
+
+        if !numerator.is_one() || (theta.is_zero() && r.is_one()) {
+            term += &numerator.to_string();
+        }
+        let half = BigRational::new(1.into(), 2.into());
+
+        if *theta == half {
+            term.push('i');
+        }
+        if !r.is_one() {
+            term += &format!("\\sqrt{{{}}}", r);
+        }
+        match (theta.numer(), theta.denom()) {
+            _ if theta.is_zero() || *theta == half => {}
+            (n, d) if n.is_one() => term += &format!("e^{{i\\pi/{}}}", d),
+            (n, d) => term += &format!("e^{{{}i\\pi/{}}}", n, d),
+        }
+
+        match q.denom().is_one() {
+            true => latex += &term,
+            false => latex += &format!("\\frac{{{}}}{{{}}}", term, q.denom()),
+        }
+    }
+
+    latex
+}
+
+// The rendering of an expression, with how tightly it binds
+fn render(expression: &Expression) -> (String, Precedence) {
+    use Precedence::*;
+
+    match &expression.kind {
+        Kind::Scalar(c) => {
+            let latex = complex(*c);
+            let precedence = match (c.re, c.im) {
+                (re, im) if re != 0.0 && im != 0.0 => Sum,
+                _ if latex.starts_with('-') => Negation,
+                _ => Atom,
+            };
+
+            (latex, precedence)
+        }
+        Kind::Constant(constant) => (constant_name(*constant).to_string(), Atom),
+        Kind::Bra(bra) => (format!("\\langle {}\\vert", bra), Atom),
+        Kind::Ket(ket) => (format!("\\vert {}\\rangle", ket), Atom),
+        Kind::NamedBra(name) => (format!("\\langle {}\\vert", name), Atom),
+        Kind::NamedKet(name) => (format!("\\vert {}\\rangle", name), Atom),
+        Kind::Outer(ket, bra) => (
+            format!("\\vert {}\\rangle\\langle {}\\vert", ket, bra),
+            Atom,
+        ),
+        Kind::Inner(a, b) => (
+            format!("\\langle {}\\vert {}\\rangle", label(a), label(b)),
+            Atom,
+        ),
+        Kind::AdditiveInverse(expr) => (format!("-{}", operand(expr, Fraction)), Negation),
+        Kind::Dagger(expr) => (format!("{}^\\dagger", operand(expr, Atom)), Power),
+        Kind::Pow(base, exponent) => (
+            format!("{}^{{{}}}", operand(base, Atom), bare(exponent)),
+            Power,
+        ),
+        Kind::Mul(a, b) => {
+            let right = match b.kind {
+                Kind::Mul(..) => operand(b, Product),
+                _ => operand(b, Fraction),
+            };
+            // Numbers would run into each other, and parentheses need no space
+            let separator = match (&b.kind, right.starts_with('(')) {
+                (Kind::Scalar(_), _) => " \\cdot ",
+                (_, true) => "",
+                (_, false) => " ",
+            };
+
+            (
+                format!("{}{}{}", operand(a, Product), separator, right),
+                Product,
+            )
+        }
+        Kind::Kronecker(a, b) => {
+            let right = match b.kind {
+                Kind::Kronecker(..) => operand(b, Product),
+                _ => operand(b, Fraction),
+            };
+
+            (
+                format!("{} \\otimes {}", operand(a, Product), right),
+                Product,
+            )
+        }
+        Kind::Div(a, b) => (format!("\\frac{{{}}}{{{}}}", bare(a), bare(b)), Fraction),
+        Kind::Add(a, b) => (
+            format!("{} + {}", operand(a, Sum), operand(b, Product)),
+            Sum,
+        ),
+        Kind::Sub(a, b) => (
+            format!("{} - {}", operand(a, Sum), operand(b, Product)),
+            Sum,
+        ),
+        Kind::Commutator(a, b) => (format!("[{}, {}]", bare(a), bare(b)), Atom),
+        Kind::Anticommutator(a, b) => (format!("\\{{{}, {}\\}}", bare(a), bare(b)), Atom),
+        Kind::Parenthised(expr) => (format!("({})", bare(expr)), Atom),
+        Kind::Norm(expr) => (format!("\\lVert {} \\rVert", bare(expr)), Atom),
+        Kind::Function(function, args) => function_call(*function, args),
+        Kind::Pauli(factors) => {
+            let factors = factors
+                .iter()
+                .map(|(pauli, site)| format!("{}_{{{}}}", pauli_name(*pauli), index(site)))
+                .collect::<Vec<String>>();
+
+            match factors.len() {
+                1 => (factors.join(" "), Atom),
+                _ => (factors.join(" "), Product),
+            }
+        }
+        Kind::Sum(variable, from, to, body) => (
+            format!(
+                "\\sum_{{{}={}}}^{{{}}} {}",
+                variable,
+                index(from),
+                index(to),
+                operand(body, Product)
+            ),
+            Sum,
+        ),
+        Kind::Let(variable, value, body) => (
+            format!("{} = {};\\ {}", variable, index(value), bare(body)),
+            Sum,
+        ),
+        Kind::LetState(name, value, body) => {
+            (format!("{} = {};\\ {}", name, bare(value), bare(body)), Sum)
+        }
+    }
+}
+
+// Renders an operand, in parentheses when it binds less tightly than required
+fn operand(expression: &Expression, precedence: Precedence) -> String {
+    match render(expression) {
+        (latex, p) if p < precedence => format!("({})", latex),
+        (latex, _) => latex,
+    }
+}
+
+// Renders an expression that is already delimited, as in a fraction or an
+// exponent, without its outer parentheses
+fn bare(expression: &Expression) -> String {
+    match &expression.kind {
+        Kind::Parenthised(expr) => bare(expr),
+        _ => render(expression).0,
+    }
+}
+
+// The label of a side of an inner product: 0 in \langle 0\vert 1\rangle
+fn label(expression: &Expression) -> String {
+    match &expression.kind {
+        Kind::Bra(label) | Kind::Ket(label) | Kind::NamedBra(label) | Kind::NamedKet(label) => {
+            label.clone()
+        }
+        _ => bare(expression),
+    }
+}
+
+fn function_call(function: Function, args: &[Expression]) -> (String, Precedence) {
+    let args_latex = args.iter().map(bare).collect::<Vec<String>>().join(", ");
+
+    match function {
+        Function::Sqrt => (format!("\\sqrt{{{}}}", args_latex), Precedence::Atom),
+        Function::Abs => (format!("\\lvert {}\\rvert", args_latex), Precedence::Atom),
+        Function::Conj => (format!("\\overline{{{}}}", args_latex), Precedence::Atom),
+        Function::Inv => (
+            format!("{}^{{-1}}", operand(&args[0], Precedence::Atom)),
+            Precedence::Power,
+        ),
+        Function::Exp | Function::Sin | Function::Cos | Function::Ln | Function::Det => (
+            format!("\\{}({})", function.name(), args_latex),
+            Precedence::Atom,
+        ),
+        Function::Re => (
+            format!("\\operatorname{{Re}}({})", args_latex),
+            Precedence::Atom,
+        ),
+        Function::Im => (
+            format!("\\operatorname{{Im}}({})", args_latex),
+            Precedence::Atom,
+        ),
+        _ => (
+            format!(
+                "\\operatorname{{{}}}({})",
+                function.name().replace('_', "\\_"),
+                args_latex
+            ),
+            Precedence::Atom,
+        ),
+    }
+}
+
+fn constant_name(constant: Constant) -> &'static str {
+    match constant {
+        Constant::Pi => "\\pi",
+        Constant::E => "e",
+        Constant::I => "I",
+        Constant::X => "X",
+        Constant::Y => "Y",
+        Constant::Z => "Z",
+        Constant::H => "H",
+        Constant::Cnot => "\\mathrm{CNOT}",
+    }
+}
+
+fn pauli_name(pauli: Pauli) -> &'static str {
+    match pauli {
+        Pauli::I => "I",
+        Pauli::X => "X",
+        Pauli::Y => "Y",
+        Pauli::Z => "Z",
+    }
+}
+
+// Site of a Pauli operator or bound of a sum, such as i+1
+fn index(index: &Index) -> String {
+    let term = |index: &Index| match index {
+        Index::Add(..) | Index::Sub(..) => format!("({})", self::index(index)),
+        _ => self::index(index),
+    };
+
+    match index {
+        Index::Number(n) => n.to_string(),
+        Index::Variable(variable) => variable.clone(),
+        Index::Add(a, b) => format!("{}+{}", self::index(a), self::index(b)),
+        Index::Sub(a, b) => format!("{}-{}", self::index(a), term(b)),
+        Index::Mul(a, b) => format!("{} {}", term(a), term(b)),
+    }
+}
@@ -1,3 +1,10 @@
+mod circuit;
+mod latex;
+
+pub use circuit::{ToQasm, ToQuil};
+pub use latex::ToLatex;
+
+use crate::symbolic::ExactTensor;
 use num::complex::Complex64;
 use tensor::Tensor;
 
@@ -5,10 +12,12 @@ pub trait ToRust {
     fn to_rust(&self, suffix: &str) -> String;
 }
 
+// Exact amplitudes such as 1/√2 round to the same floats as the constants
+// in std::f64::consts, which clippy would flag in the caller's crate
 impl ToRust for Tensor {
     fn to_rust(&self, suffix: &str) -> String {
         format!(
-            "({}, {}){}",
+            "{{ #[allow(clippy::approx_constant)] let tensor = ({}, {}); tensor }}{}",
             self.shape.to_rust(""),
             self.data.to_rust(""),
             suffix,
@@ -16,6 +25,13 @@ impl ToRust for Tensor {
     }
 }
 
+// Exact amplitudes are only rounded to floats here
+impl ToRust for ExactTensor {
+    fn to_rust(&self, suffix: &str) -> String {
+        Tensor::from(self).to_rust(suffix)
+    }
+}
+
 impl ToRust for &Complex64 {
     fn to_rust(&self, _: &str) -> String {
         format!("({}f64, {}f64)", self.re, self.im)
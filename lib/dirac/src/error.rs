@@ -0,0 +1,28 @@
+//! Errors in dirac notation input, located in the source text.
+
+use crate::expression::Span;
+use std::fmt::{self, Display};
+
+/// An error along with the part of the input that caused it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Error {
+    pub fn new(message: impl Into<String>, span: Span) -> Error {
+        Error {
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,12 +1,126 @@
-use crate::tensor::Tensor;
+use crate::builtins::{Constant, Function};
+use crate::error::Error;
+use crate::tensor::{AsTensor, KronOp, KroneckerProduct, Pauli, PauliString, Tensor};
 use num::complex::Complex64;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
 
-#[derive(Debug)]
-pub enum Expression {
+// Values of the integer variables bound by `name = index;` and sums
+pub(crate) type Variables = HashMap<String, i64>;
+
+// Largest number of terms of a sum, which are all checked and evaluated one
+// by one
+const MAX_TERMS: i128 = 1 << 16;
+
+/// Integer expressions used for qubit indices and summation bounds
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Index {
+    Number(i64),
+    Variable(String),
+    Add(Box<Index>, Box<Index>),
+    Sub(Box<Index>, Box<Index>),
+    Mul(Box<Index>, Box<Index>),
+}
+
+impl Index {
+    pub fn value(&self, variables: &Variables) -> i64 {
+        match self.try_value(variables) {
+            Ok(n) => n,
+            Err(message) => panic!("{}", message),
+        }
+    }
+
+    // Value of the index, or a message naming the first unbound variable
+    pub(crate) fn try_value(&self, variables: &Variables) -> Result<i64, String> {
+        match self {
+            Self::Number(n) => Ok(*n),
+            Self::Variable(name) => match variables.get(name) {
+                Some(n) => Ok(*n),
+                None => Err(format!("unbound variable `{}`", name)),
+            },
+            Self::Add(a, b) => Ok(a.try_value(variables)? + b.try_value(variables)?),
+            Self::Sub(a, b) => Ok(a.try_value(variables)? - b.try_value(variables)?),
+            Self::Mul(a, b) => Ok(a.try_value(variables)? * b.try_value(variables)?),
+        }
+    }
+
+    // Value as a qubit index
+    pub(crate) fn site(&self, variables: &Variables) -> Result<usize, String> {
+        match self.try_value(variables)? {
+            n if n >= 0 => Ok(n as usize),
+            n => Err(format!("qubit index {} is negative", n)),
+        }
+    }
+}
+
+// Scope an expression is walked in, where the named states are tensors when
+// evaluating and types when checking
+pub(crate) struct Context<T = Tensor> {
+    pub(crate) variables: Variables,
+    // States bound by `ψ = expr;`, as the ket |ψ>
+    pub(crate) states: HashMap<String, T>,
+    // Number of qubits Pauli strings act on
+    pub(crate) width: usize,
+}
+
+impl<T> Context<T> {
+    pub(crate) fn new(expression: &Expression) -> Context<T> {
+        Context::with_width(match expression.register_width() {
+            Ok(width) => width,
+            Err(error) => panic!("{}", error),
+        })
+    }
+
+    pub(crate) fn with_width(width: usize) -> Context<T> {
+        Context {
+            variables: Variables::new(),
+            states: HashMap::new(),
+            width,
+        }
+    }
+}
+
+/// Byte range of the source text an expression was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    // Smallest span covering both spans
+    pub fn join(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+}
+
+/// A node of the syntax tree along with the part of the input it spans
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Expression {
+    pub kind: Kind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Kind {
     Scalar(Complex64),
+    Constant(Constant),
 
-    Bra(Tensor),
-    Ket(Tensor),
+    // Basis states labelled by a sequence of 01+- characters, materialised
+    // only when evaluated
+    Bra(String),
+    Ket(String),
+    // States bound to a name by `ψ = expr;`, written ⟨ψ| and |ψ⟩
+    NamedBra(String),
+    NamedKet(String),
 
     AdditiveInverse(Box<Expression>),
     Dagger(Box<Expression>),
@@ -16,31 +130,376 @@ pub enum Expression {
     Add(Box<Expression>, Box<Expression>),
     Sub(Box<Expression>, Box<Expression>),
     Kronecker(Box<Expression>, Box<Expression>),
+    Pow(Box<Expression>, Box<Expression>),
 
     Inner(Box<Expression>, Box<Expression>),
-    Outer(Tensor, Tensor),
+    Outer(String, String),
+
+    Commutator(Box<Expression>, Box<Expression>),
+    Anticommutator(Box<Expression>, Box<Expression>),
 
     Parenthised(Box<Expression>),
     Norm(Box<Expression>),
+    Function(Function, Vec<Expression>),
+
+    // Product of Pauli operators on the given qubits, e.g. Z0 Z1
+    Pauli(Vec<(Pauli, Index)>),
+    // Sum of the expression over an inclusive range of the variable
+    Sum(String, Index, Index, Box<Expression>),
+    // Binds a variable for the rest of the expression
+    Let(String, Index, Box<Expression>),
+    // Binds a named ket for the rest of the expression
+    LetState(String, Box<Expression>, Box<Expression>),
 }
 
 impl Expression {
-    pub fn compute(&self) -> Tensor {
+    pub fn new(kind: Kind, span: Span) -> Expression {
+        Expression { kind, span }
+    }
+
+    // Evaluates the expression, reporting values out of range such as a
+    // singular matrix being inverted with the span of the node at fault
+    pub fn compute(&self) -> Result<Tensor, Error> {
+        self.evaluate(&mut Context::new(self))
+    }
+
+    pub(crate) fn evaluate(&self, context: &mut Context) -> Result<Tensor, Error> {
+        let error = |message: String| Error::new(message, self.span);
+
+        Ok(match &self.kind {
+            Kind::Scalar(c) => Tensor::new(vec![*c], (1, 1)),
+            Kind::Constant(constant) => constant.value(),
+            Kind::Bra(bra) => basis(bra).dag(),
+            Kind::Ket(ket) => basis(ket),
+            Kind::NamedBra(name) => state(&context.states, name).map_err(error)?.dag(),
+            Kind::NamedKet(name) => state(&context.states, name).map_err(error)?.clone(),
+            Kind::AdditiveInverse(_)
+            | Kind::Dagger(_)
+            | Kind::Mul(..)
+            | Kind::Kronecker(..)
+            | Kind::Parenthised(_) => self.value(context)?.dense(),
+            Kind::Div(a, b) => {
+                let (a, b) = (a.evaluate(context)?, b.evaluate(context)?);
+
+                // Right division by a matrix multiplies by its inverse
+                match b.item() {
+                    Some(c) => a / c,
+                    None => {
+                        a * b
+                            .inv()
+                            .map_err(|e| error(format!("cannot divide by a matrix: {}", e)))?
+                    }
+                }
+            }
+            Kind::Add(a, b) => a.evaluate(context)? + b.evaluate(context)?,
+            Kind::Sub(a, b) => a.evaluate(context)? - b.evaluate(context)?,
+            Kind::Pow(base, exponent) => {
+                let base = base.evaluate(context)?;
+                let value = exponent.evaluate(context)?;
+                let value = match value.item() {
+                    Some(c) => c,
+                    None => panic!("exponent must be a scalar, got shape {:?}", value.shape),
+                };
+                let n = integer(value).map_err(|message| Error::new(message, exponent.span))?;
+
+                pow(&base, value, n).map_err(error)?
+            }
+            Kind::Inner(a, b) => {
+                Tensor::new(vec![a.evaluate(context)? | b.evaluate(context)?], (1, 1))
+            }
+            Kind::Outer(ket, bra) => basis(ket) * basis(bra).dag(),
+            Kind::Commutator(a, b) => a.evaluate(context)?.commutator(&b.evaluate(context)?),
+            Kind::Anticommutator(a, b) => {
+                a.evaluate(context)?.anticommutator(&b.evaluate(context)?)
+            }
+            Kind::Norm(expr) => Tensor::new(vec![expr.evaluate(context)?.norm().into()], (1, 1)),
+            Kind::Function(function, args) => function
+                .apply(
+                    &args
+                        .iter()
+                        .map(|arg| arg.evaluate(context))
+                        .collect::<Result<Vec<Tensor>, Error>>()?,
+                )
+                .map_err(error)?,
+            Kind::Pauli(factors) => {
+                pauli_string(factors, &context.variables, context.width).map_err(error)?
+            }
+            Kind::Sum(variable, from, to, body) => {
+                let (from, to) = (from.value(&context.variables), to.value(&context.variables));
+                let shadowed = context.variables.get(variable).cloned();
+
+                let sum = terms(variable, from, to, self.span)?.try_fold(
+                    None,
+                    |sum: Option<Tensor>, value| {
+                        context.variables.insert(variable.clone(), value);
+                        let term = body.evaluate(context)?;
+
+                        Ok::<_, Error>(Some(match sum {
+                            Some(sum) => sum + term,
+                            None => term,
+                        }))
+                    },
+                );
+
+                restore(&mut context.variables, variable, shadowed);
+
+                match sum? {
+                    Some(sum) => sum,
+                    None => {
+                        return Err(error(format!(
+                            "empty sum over {} from {} to {}",
+                            variable, from, to
+                        )))
+                    }
+                }
+            }
+            Kind::Let(variable, value, body) => {
+                let value = value.value(&context.variables);
+                let shadowed = context.variables.insert(variable.clone(), value);
+                let result = body.evaluate(context);
+
+                restore(&mut context.variables, variable, shadowed);
+
+                result?
+            }
+            Kind::LetState(name, value, body) => {
+                let value = value.evaluate(context)?;
+                let shadowed = context.states.insert(name.clone(), value);
+                let result = body.evaluate(context);
+
+                restore(&mut context.states, name, shadowed);
+
+                result?
+            }
+        })
+    }
+
+    // Evaluates the nodes that can keep Kronecker products of operators as
+    // their factors, so that a product of gates applied to a state is never
+    // built as a full matrix. Everything else is evaluated densely.
+    fn value(&self, context: &mut Context) -> Result<Value, Error> {
+        Ok(match &self.kind {
+            Kind::AdditiveInverse(expr) => match expr.value(context)? {
+                Value::Kron(op) => Value::Kron(op.scale(-Complex64::new(1.0, 0.0))),
+                Value::Dense(tensor) => Value::Dense(-tensor),
+            },
+            Kind::Dagger(expr) => match expr.value(context)? {
+                Value::Kron(op) => Value::Kron(op.dag()),
+                Value::Dense(tensor) => Value::Dense(tensor.dag()),
+            },
+            Kind::Mul(a, b) => match (a.value(context)?, b.value(context)?) {
+                (Value::Kron(a), Value::Kron(b)) => match a.compose(&b) {
+                    Some(op) => Value::Kron(op),
+                    None => Value::Dense(&a * &b.to_tensor()),
+                },
+                (Value::Kron(op), Value::Dense(x)) => match x.item() {
+                    Some(c) => Value::Kron(op.scale(c)),
+                    None => Value::Dense(&op * &x),
+                },
+                (Value::Dense(x), Value::Kron(op)) => match x.item() {
+                    Some(c) => Value::Kron(op.scale(c)),
+                    None => Value::Dense(&x * &op),
+                },
+                (Value::Dense(a), Value::Dense(b)) => Value::Dense(a * b),
+            },
+            Kind::Kronecker(a, b) => match (a.value(context)?, b.value(context)?) {
+                (a, b) if a.is_operator() && b.is_operator() => {
+                    Value::Kron(a.into_kron().kron(b.into_kron()))
+                }
+                (a, b) => Value::Dense(a.dense().prod(&b.dense())),
+            },
+            Kind::Parenthised(expr) => expr.value(context)?,
+            _ => Value::Dense(self.evaluate(context)?),
+        })
+    }
+
+    // Number of qubits Pauli strings act on: one past the highest qubit index
+    // used anywhere in the expression, or None without Pauli strings. Unbound
+    // variables and negative indices are reported with the span of the node
+    // using them.
+    pub(crate) fn width(&self, variables: &mut Variables) -> Result<Option<usize>, Error> {
+        let value = |index: &Index, variables: &Variables| {
+            index
+                .try_value(variables)
+                .map_err(|message| Error::new(message, self.span))
+        };
+
+        match &self.kind {
+            Kind::Scalar(_)
+            | Kind::Constant(_)
+            | Kind::Bra(_)
+            | Kind::Ket(_)
+            | Kind::NamedBra(_)
+            | Kind::NamedKet(_)
+            | Kind::Outer(..) => Ok(None),
+            Kind::AdditiveInverse(expr)
+            | Kind::Dagger(expr)
+            | Kind::Parenthised(expr)
+            | Kind::Norm(expr) => expr.width(variables),
+            Kind::Mul(a, b)
+            | Kind::Div(a, b)
+            | Kind::Add(a, b)
+            | Kind::Sub(a, b)
+            | Kind::Kronecker(a, b)
+            | Kind::Pow(a, b)
+            | Kind::Inner(a, b)
+            | Kind::Commutator(a, b)
+            | Kind::Anticommutator(a, b)
+            | Kind::LetState(_, a, b) => Ok(a.width(variables)?.max(b.width(variables)?)),
+            Kind::Function(_, args) => args
+                .iter()
+                .try_fold(None, |width, arg| Ok(width.max(arg.width(variables)?))),
+            Kind::Pauli(factors) => factors.iter().try_fold(None, |width, (_, site)| {
+                let site = site
+                    .site(variables)
+                    .map_err(|message| Error::new(message, self.span))?;
+
+                Ok(width.max(Some(site + 1)))
+            }),
+            Kind::Sum(variable, from, to, body) => {
+                let (from, to) = (value(from, variables)?, value(to, variables)?);
+                let shadowed = variables.get(variable).cloned();
+
+                let width = terms(variable, from, to, self.span)?.try_fold(None, |width, value| {
+                    variables.insert(variable.clone(), value);
+                    Ok(width.max(body.width(variables)?))
+                });
+
+                restore(variables, variable, shadowed);
+
+                width
+            }
+            Kind::Let(variable, value_index, body) => {
+                let value = value(value_index, variables)?;
+                let shadowed = variables.insert(variable.clone(), value);
+                let width = body.width(variables);
+
+                restore(variables, variable, shadowed);
+
+                width
+            }
+        }
+    }
+}
+
+// Result of evaluating a node, with Kronecker products of operators kept as
+// their factors until the full matrix is needed
+enum Value {
+    Dense(Tensor),
+    Kron(KronOp),
+}
+
+impl Value {
+    fn dense(self) -> Tensor {
+        match self {
+            Self::Dense(tensor) => tensor,
+            Self::Kron(op) => op.to_tensor(),
+        }
+    }
+
+    fn into_kron(self) -> KronOp {
         match self {
-            Self::Scalar(c) => Tensor::new(vec![*c], (1, 1)),
-            Self::Bra(bra) => bra.dag(),
-            Self::Ket(ket) => ket.clone(),
-            Self::AdditiveInverse(expr) => &expr.compute() * -1.,
-            Self::Dagger(expr) => expr.compute().dag(),
-            Self::Mul(a, b) => a.compute() * b.compute(),
-            Self::Div(a, b) => a.compute() / b.compute(),
-            Self::Add(a, b) => a.compute() + b.compute(),
-            Self::Sub(a, b) => a.compute() - b.compute(),
-            Self::Kronecker(a, b) => a.compute().prod(&b.compute()),
-            Self::Inner(a, b) => Tensor::new(vec![a.compute() | b.compute()], (1, 1)),
-            Self::Outer(a, b) => a * &b.dag(),
-            Self::Parenthised(expr) => expr.compute(),
-            Self::Norm(expr) => Tensor::new(vec![expr.compute().norm().into()], (1, 1)),
+            Self::Dense(tensor) => KronOp::new(vec![tensor]),
+            Self::Kron(op) => op,
         }
     }
+
+    // Kets, bras and scalars are multiplied out right away
+    fn is_operator(&self) -> bool {
+        match self {
+            Self::Dense(tensor) => tensor.shape.0 > 1 && tensor.shape.1 > 1,
+            Self::Kron(_) => true,
+        }
+    }
+}
+
+// Builds a tensor from a sequence of 01+- characters by applying a sequence of
+// Kronecker products.
+fn basis(label: &str) -> Tensor {
+    label
+        .chars()
+        .map(|c| c.as_tensor())
+        .collect::<Vec<Tensor>>()
+        .prod()
+}
+
+// Product of Pauli operators on a register of the given width
+pub(crate) fn pauli_string(
+    factors: &[(Pauli, Index)],
+    variables: &Variables,
+    width: usize,
+) -> Result<Tensor, String> {
+    Ok(factors
+        .iter()
+        .map(|(op, site)| Ok(PauliString::single(width, site.site(variables)?, *op)))
+        .collect::<Result<Vec<PauliString>, String>>()?
+        .iter()
+        .fold(PauliString::identity(width), |product, factor| {
+            &product * factor
+        })
+        .to_tensor())
+}
+
+// The ket bound to a name
+pub(crate) fn state<'a, T>(states: &'a HashMap<String, T>, name: &str) -> Result<&'a T, String> {
+    states
+        .get(name)
+        .ok_or_else(|| format!("unbound state `{}`", name))
+}
+
+// Values the variable of a sum takes, as long as there are not too many of
+// them to go through
+pub(crate) fn terms(
+    variable: &str,
+    from: i64,
+    to: i64,
+    span: Span,
+) -> Result<RangeInclusive<i64>, Error> {
+    match to as i128 - from as i128 + 1 {
+        n if n > MAX_TERMS => Err(Error::new(
+            format!(
+                "sum over {} from {} to {} has more than {} terms",
+                variable, from, to, MAX_TERMS
+            ),
+            span,
+        )),
+        _ => Ok(from..=to),
+    }
+}
+
+// Puts back the value a variable or state had before it was bound in a
+// nested scope
+pub(crate) fn restore<T>(scope: &mut HashMap<String, T>, name: &str, shadowed: Option<T>) {
+    match shadowed {
+        Some(value) => scope.insert(name.to_string(), value),
+        None => scope.remove(name),
+    };
+}
+
+// The exponent as an integer, if it is one. Integers beyond i32 are reported
+// rather than saturated into a wrong power.
+fn integer(exponent: Complex64) -> Result<Option<i32>, String> {
+    if exponent.im != 0.0 || exponent.re.fract() != 0.0 {
+        return Ok(None);
+    }
+
+    match exponent.re >= i32::MIN as f64 && exponent.re <= i32::MAX as f64 {
+        true => Ok(Some(exponent.re as i32)),
+        false => Err(format!("integer exponent {} is out of range", exponent.re)),
+    }
+}
+
+// Raises a scalar to a complex power, or a square operator to an integer power
+fn pow(base: &Tensor, exponent: Complex64, integer: Option<i32>) -> Result<Tensor, String> {
+    match (base.item(), integer) {
+        (Some(c), Some(n)) => Ok(Tensor::new(vec![c.powi(n)], (1, 1))),
+        (Some(c), None) => Ok(Tensor::new(vec![c.powc(exponent)], (1, 1))),
+        // Negative powers are powers of the inverse
+        (None, Some(n)) if n < 0 => match base.inv() {
+            Ok(inverse) => Ok(inverse.powu(n.unsigned_abs())),
+            Err(e) => Err(format!("cannot raise to a negative power: {}", e)),
+        },
+        (None, Some(n)) => Ok(base.powi(n)),
+        (None, None) => Err("operators can only be raised to integer powers".to_string()),
+    }
 }
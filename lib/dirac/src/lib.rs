@@ -1,18 +1,31 @@
 //! The `q30d` crate provides macros for generating qubits state using Dirac
 //! notation.
 //!
-//! All register states generated using macros are computed at compile time.
+//! All register states generated using macros are computed at compile time,
+//! exactly when the amplitudes are built from rationals, square roots and
+//! roots of unity.
 
 extern crate proc_macro;
 extern crate tensor;
 
-use codegen::ToRust;
-use proc_macro::TokenStream;
+use codegen::{ToLatex, ToQasm, ToQuil, ToRust};
+use error::Error;
+use expression::Expression;
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
 use std::str::FromStr;
 
+mod builtins;
+mod checker;
 mod codegen;
+mod error;
 mod expression;
+mod optimizer;
 mod parser;
+mod symbolic;
+
+// The QASM front end is used by the REPL, and only tested from here
+#[cfg(test)]
+mod qasm;
 
 #[cfg(test)]
 mod tests;
@@ -21,24 +34,37 @@ mod tests;
 ///
 /// The standard ket notation can be used: `|01-+>`
 /// The kronecker product ⊗ is represented by `x`
-/// Standard tensor operations are supported: +, -, *, /
+/// Expressions copied from papers, with `⊗`, `†`, `⟨0|`, `|1⟩`, `√2` and `·`,
+/// are passed as a string literal since those are not Rust tokens:
+/// `dirac!("(|0⟩ ⊗ |1⟩ + |1⟩ ⊗ |0⟩)/√2")`. Kets can be named, as in
+/// `dirac!("ψ = (|0⟩ + |1⟩)/√2; ⟨ψ|X|ψ⟩")`, where `⟨ψ|` is the dagger of
+/// `|ψ⟩`. Greek names such as `θ` are accepted either way, but quoting them
+/// avoids rustc's `mixed_script_confusables` lint
+/// Standard tensor operations are supported: +, -, *, /, ^
+/// Commutators are written `[A, B]` and anticommutators `{A, B}`
+/// Dividing by a square operator multiplies by its inverse, and `tr`, `det`,
+/// `inv` (or `A^-1`) and `rank` are available as functions
+/// Scalar functions (`sqrt`, `exp`, `sin`, `cos`, `ln`, `abs`, `conj`, `re`,
+/// `im`) and the constants `pi` and `e` are available. `sqrt`, `exp`, `ln`,
+/// `sin` and `cos` of a square operator are matrix functions, and the single
+/// qubit operators `I`, `X`, `Y`, `Z` and `H`, along with `CNOT`, are
+/// predefined, so `exp(-i * pi/4 * X x X)` is evaluated at compile time.
+/// Quantum information functions take kets or density operators on qubit
+/// registers: `ptrace(ρ, k)` traces out qubit k, `pt(ρ, k)` transposes it,
+/// `entropy(ρ)`, `renyi(ρ, α)`,
+/// `concurrence(ρ)`, `negativity(ρ, k)`, `mutual_info(ρ, k)` (qubit k against
+/// the rest), `fidelity(ρ, σ)` and `trace_distance(ρ, σ)`.
+/// `normalize(ψ)` scales a tensor to unit norm. Passing `normalize` as a
+/// second macro argument normalizes the result: `dirac!(|0> + |1>, normalize)`
 #[proc_macro]
 pub fn dirac(input: TokenStream) -> TokenStream {
-    let input_string = input.to_string();
+    let input_string = source(&input);
 
-    match parser::dirac(&input_string) {
-        Ok((_, expression)) => {
-            // Execute the expression
-            let tensor = expression.compute();
-
-            // Nothing we can do about stream errors at this point since this is
-            // running inside the compiler, so we just unwrap.
-            TokenStream::from_str(&tensor.to_rust("")).unwrap()
-        }
-        Err(e) => panic!(
-            "Cannot interpret `{}` as dirac notation: {}",
-            input_string, e
-        ),
+    match parse(&input_string).and_then(|expression| generate(expression, "")) {
+        // Nothing we can do about stream errors at this point since this is
+        // running inside the compiler, so we just unwrap.
+        Ok(code) => TokenStream::from_str(&code).unwrap(),
+        Err(error) => compile_error(input, &input_string, &error),
     }
 }
 
@@ -48,7 +74,7 @@ pub fn dirac(input: TokenStream) -> TokenStream {
 /// the macro to convert the data transfer type to your custom tensor runtime
 /// type.
 ///
-/// ```
+/// ```ignore
 /// use tensor::TensorData;
 ///
 /// trait ToTensor {
@@ -66,20 +92,229 @@ pub fn dirac(input: TokenStream) -> TokenStream {
 /// <https://rust-lang.github.io/rfcs/0445-extension-trait-conventions.html>
 #[proc_macro]
 pub fn xdirac(input: TokenStream) -> TokenStream {
-    let input_string = input.to_string();
+    let input_string = source(&input);
+
+    match parse(&input_string).and_then(|expression| generate(expression, ".to_tensor()")) {
+        // Nothing we can do about stream errors at this point since this is
+        // running inside the compiler, so we just unwrap.
+        Ok(code) => TokenStream::from_str(&code).unwrap(),
+        Err(error) => compile_error(input, &input_string, &error),
+    }
+}
+
+/// Generates an OpenQASM 3 program, as a string literal, from gates applied to
+/// a basis state: `qasm!(CNOT (H x I) |00>)` prepares a Bell state.
+///
+/// The state is prepared from `|0...0>` and the gates are applied from right
+/// to left. Each gate is a Kronecker product of `I`, `X`, `Y`, `Z`, `H` and
+/// `CNOT`, where the leftmost factor acts on qubit 0.
+#[proc_macro]
+pub fn qasm(input: TokenStream) -> TokenStream {
+    program(input, Expression::to_qasm)
+}
+
+/// Generates a Quil program, as a string literal, from gates applied to a
+/// basis state, in the same way as the `qasm!` macro.
+#[proc_macro]
+pub fn quil(input: TokenStream) -> TokenStream {
+    program(input, Expression::to_quil)
+}
+
+/// Renders Dirac notation in LaTeX, as a string literal:
+/// `latex!(1/sqrt(2) (|0> + |1>))` is
+/// `\frac{1}{\sqrt{2}}(\vert 0\rangle + \vert 1\rangle)`.
+#[proc_macro]
+pub fn latex(input: TokenStream) -> TokenStream {
+    program(input, |expression| Ok(expression.to_latex()))
+}
+
+// Expands to the text generated from the macro input, such as a program
+fn program(input: TokenStream, generate: fn(&Expression) -> Result<String, Error>) -> TokenStream {
+    let input_string = source(&input);
+
+    match parse(&input_string).and_then(|expression| generate(&expression)) {
+        Ok(program) => TokenTree::from(Literal::string(&program)).into(),
+        Err(error) => compile_error(input, &input_string, &error),
+    }
+}
+
+// The text of the macro input. Symbols such as ⊗, † and ⟨ are not Rust
+// tokens, so an expression using them is passed as a string literal instead,
+// whose contents are parsed: dirac!("|0⟩ ⊗ |1⟩", normalize)
+fn source(input: &TokenStream) -> String {
+    let mut trees = input.clone().into_iter();
+
+    match trees.next() {
+        Some(TokenTree::Literal(literal)) => match unquote(&literal.to_string()) {
+            Some(text) => match TokenStream::from_iter(trees) {
+                rest if rest.is_empty() => text,
+                rest => format!("{} {}", text, rest),
+            },
+            None => input.to_string(),
+        },
+        _ => input.to_string(),
+    }
+}
+
+// The contents of a string literal token, "..." or r#"..."#, or None for other
+// literals
+fn unquote(literal: &str) -> Option<String> {
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = &raw[..raw.len() - raw.trim_start_matches('#').len()];
+
+        return raw
+            .strip_prefix(hashes)?
+            .strip_prefix('"')?
+            .strip_suffix(hashes)?
+            .strip_suffix('"')
+            .map(String::from);
+    }
+
+    let mut chars = literal
+        .strip_prefix('"')?
+        .strip_suffix('"')?
+        .chars()
+        .peekable();
+    let mut text = String::new();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+
+        match chars.next()? {
+            'n' => text.push('\n'),
+            'r' => text.push('\r'),
+            't' => text.push('\t'),
+            '0' => text.push('\0'),
+            '\n' => {
+                // A line continuation skips the indentation of the next line
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            }
+            'x' => {
+                let code = chars.by_ref().take(2).collect::<String>();
+                text.push(u8::from_str_radix(&code, 16).ok()? as char);
+            }
+            'u' => {
+                let code = chars
+                    .by_ref()
+                    .skip(1)
+                    .take_while(|c| *c != '}')
+                    .collect::<String>();
+                text.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            c => text.push(c),
+        }
+    }
+
+    Some(text)
+}
+
+// Parses the macro input and checks the shapes of its operands, so that
+// mistakes are reported as compile errors rather than evaluation panics
+fn parse(input: &str) -> Result<Expression, Error> {
+    let expression = parser::dirac(input)?;
+    expression.check()?;
+
+    Ok(expression)
+}
+
+// Executes the expression, exactly when possible so that amplitudes are
+// rounded only once, and generates the code of the result. Infinite and NaN
+// amplitudes, as in 1/0, have no float literal and are reported instead.
+fn generate(expression: Expression, suffix: &str) -> Result<String, Error> {
+    if let Some(tensor) = expression.exact() {
+        return Ok(tensor.to_rust(suffix));
+    }
+
+    let span = expression.span;
+    let tensor = expression.optimize().compute()?;
+
+    match tensor
+        .data
+        .iter()
+        .all(|c| c.re.is_finite() && c.im.is_finite())
+    {
+        true => Ok(tensor.to_rust(suffix)),
+        false => Err(Error::new(
+            "the result has infinite or NaN amplitudes",
+            span,
+        )),
+    }
+}
+
+// Reports an error in the macro input as a compile_error! pointing at the
+// input token the error starts in
+fn compile_error(input: TokenStream, source: &str, error: &Error) -> TokenStream {
+    let mut tokens = Vec::new();
+    token_offsets(input, source, &mut 0, &mut tokens);
+
+    let span = tokens
+        .iter()
+        .rev()
+        .find(|(offset, _)| *offset <= error.span.start)
+        .map_or_else(Span::call_site, |(_, span)| *span);
+
+    let mut message = Literal::string(&format!(
+        "Cannot interpret `{}` as dirac notation: {}",
+        source, error
+    ));
+    message.set_span(span);
+
+    let mut arguments = Group::new(Delimiter::Parenthesis, TokenTree::from(message).into());
+    arguments.set_span(span);
+
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+
+    TokenStream::from_iter([
+        TokenTree::from(Ident::new("compile_error", span)),
+        bang.into(),
+        arguments.into(),
+    ])
+}
+
+// Collects the span of each token of a stream along with the byte offset of
+// its text in the stream's string representation, which is what the parser
+// sees. Tokens are searched for in order, starting from the cursor.
+fn token_offsets(
+    stream: TokenStream,
+    source: &str,
+    cursor: &mut usize,
+    tokens: &mut Vec<(usize, Span)>,
+) {
+    let locate = |text: &str, span: Span, cursor: &mut usize, tokens: &mut Vec<_>| {
+        if let Some(offset) = source[*cursor..].find(text) {
+            tokens.push((*cursor + offset, span));
+            *cursor += offset + text.len();
+        }
+    };
+
+    for tree in stream {
+        match tree {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => {
+                        token_offsets(group.stream(), source, cursor, tokens);
+                        continue;
+                    }
+                };
 
-    match parser::dirac(&input_string) {
-        Ok((_, expression)) => {
-            // Execute the expression
-            let tensor = expression.compute();
+                locate(open, group.span_open(), cursor, tokens);
+                token_offsets(group.stream(), source, cursor, tokens);
+                locate(close, group.span_close(), cursor, tokens);
+            }
+            TokenTree::Literal(literal) => {
+                let text = literal.to_string();
+                let text = unquote(&text).unwrap_or(text);
 
-            // Nothing we can do about stream errors at this point since this is
-            // running inside the compiler, so we just unwrap.
-            TokenStream::from_str(&tensor.to_rust(".to_tensor()")).unwrap()
+                locate(&text, literal.span(), cursor, tokens)
+            }
+            tree => locate(&tree.to_string(), tree.span(), cursor, tokens),
         }
-        Err(e) => panic!(
-            "Cannot interpret `{}` as dirac notation: {}",
-            input_string, e
-        ),
     }
 }
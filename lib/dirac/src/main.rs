@@ -1,28 +1,261 @@
 extern crate tensor;
 
+use error::Error;
+use expression::{Expression, Span};
+use latex::ToLatex;
 use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process;
+use symbolic::ExactTensor;
 use tensor::Tensor;
 
+mod builtins;
+mod checker;
+mod error;
 mod expression;
+// Only the LaTeX rendering of the code generation is used by the REPL
+#[path = "codegen/latex.rs"]
+mod latex;
+mod optimizer;
 mod parser;
+mod qasm;
+mod symbolic;
 
-fn calculate(expression: &str) -> Result<Tensor, nom::Err<nom::error::Error<&str>>> {
-    match parser::dirac(expression) {
-        Ok((_, ast)) => Ok(ast.compute()),
-        Err(e) => Err(e),
+#[cfg(feature = "npy")]
+const USAGE: &str = "usage: dirac [--out FILE.npy | --out FILE.npz]
+       dirac qasm FILE.qasm [--unitary] [--out FILE.npy]";
+#[cfg(not(feature = "npy"))]
+const USAGE: &str = "usage: dirac
+       dirac qasm FILE.qasm [--unitary]";
+
+// Command line options: the REPL by default, or the circuit of a QASM file
+#[derive(Default)]
+struct Options {
+    qasm: Option<PathBuf>,
+    // Whether to compute the unitary of the circuit rather than its state
+    unitary: bool,
+    out: Option<PathBuf>,
+}
+
+fn options() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1).peekable();
+    let mut options = Options::default();
+    let path = |args: &mut dyn Iterator<Item = String>, flag: &str| {
+        args.next()
+            .map(PathBuf::from)
+            .ok_or_else(|| format!("{} expects a file name", flag))
+    };
+
+    if args.peek().map(String::as_str) == Some("qasm") {
+        args.next();
+        options.qasm = Some(path(&mut args, "qasm")?);
+    }
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => options.out = Some(path(&mut args, "--out")?),
+            "--unitary" if options.qasm.is_some() => options.unitary = true,
+            arg => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    #[cfg(not(feature = "npy"))]
+    if options.out.is_some() {
+        return Err("--out needs dirac built with the `npy` feature".to_string());
+    }
+
+    Ok(options)
+}
+
+// Evaluates an expression, printing exact results with `exact` when possible
+// and others with `float`, along with its numeric value
+fn evaluate(
+    ast: Expression,
+    exact: fn(&ExactTensor) -> String,
+    float: fn(&Tensor) -> String,
+) -> Result<(String, Tensor), Error> {
+    ast.check()?;
+
+    match ast.exact() {
+        Some(tensor) => Ok((exact(&tensor), Tensor::from(&tensor))),
+        None => {
+            let tensor = ast.optimize().compute()?;
+
+            Ok((float(&tensor), tensor))
+        }
+    }
+}
+
+fn calculate(expression: &str) -> Result<(String, Tensor), Error> {
+    evaluate(
+        parser::dirac(expression)?,
+        ExactTensor::to_string,
+        Tensor::to_string,
+    )
+}
+
+// Renders an expression and its value in LaTeX:
+// \vert +\rangle = \begin{bmatrix} \frac{\sqrt{2}}{2} \\ \frac{\sqrt{2}}{2} \end{bmatrix}
+fn latex(expression: &str) -> Result<(String, Tensor), Error> {
+    let ast = parser::dirac(expression)?;
+    let rendering = ast.to_latex();
+    let (value, tensor) = evaluate(ast, ExactTensor::to_latex, Tensor::to_latex)?;
+
+    Ok((format!("{} = {}", rendering, value), tensor))
+}
+
+// Runs a line of the REPL: an expression, or `:latex expression`
+fn run(line: &str) -> Result<(String, Tensor), Error> {
+    match line.trim_start().strip_prefix(":latex") {
+        Some(expression) => {
+            let expression = expression.trim_start();
+            let offset = line.len() - expression.len();
+
+            latex(expression).map_err(|error| {
+                let span = Span::new(error.span.start + offset, error.span.end + offset);
+
+                Error::new(error.message, span)
+            })
+        }
+        None => calculate(line),
+    }
+}
+
+// The state a circuit prepares, or its unitary
+fn simulate(source: &str, unitary: bool) -> Result<(String, Tensor), Error> {
+    let circuit = qasm::qasm(source)?;
+
+    let ast = match unitary {
+        true => circuit.unitary(),
+        false => circuit.state(),
+    };
+
+    evaluate(ast, ExactTensor::to_string, Tensor::to_string)
+}
+
+// Saves the last result to a .npy file, or all of them to a .npz file as
+// arr_0, arr_1, ... like numpy.savez
+#[cfg(feature = "npy")]
+fn save(path: &PathBuf, results: &[Tensor]) -> io::Result<()> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("npz") => {
+            let names = (0..results.len())
+                .map(|k| format!("arr_{}", k))
+                .collect::<Vec<String>>();
+
+            Tensor::write_npz(path, names.iter().map(String::as_str).zip(results))
+        }
+        _ => match results.last() {
+            Some(tensor) => tensor.write_npy(path),
+            None => Ok(()),
+        },
+    }
+}
+
+// Points at the part of the line an error comes from:
+//
+//   |0> + |1>)
+//            ^ unexpected `)`
+fn underline(line: &str, error: &Error) -> String {
+    let start = line[..error.span.start].chars().count();
+    let width = line[error.span.start..error.span.end]
+        .chars()
+        .count()
+        .max(1);
+
+    format!(
+        "{}\n{}{} {}",
+        line,
+        " ".repeat(start),
+        "^".repeat(width),
+        error
+    )
+}
+
+// Points at the line of a file an error comes from
+fn locate(path: &Path, source: &str, error: &Error) -> String {
+    let start = source[..error.span.start].rfind('\n').map_or(0, |k| k + 1);
+    let end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |k| start + k);
+    let span = Span::new(error.span.start - start, error.span.end.min(end) - start);
+
+    format!(
+        "{}:{}\n{}",
+        path.display(),
+        source[..start].matches('\n').count() + 1,
+        underline(
+            &source[start..end],
+            &Error::new(error.message.clone(), span)
+        )
+    )
+}
+
+#[cfg(feature = "npy")]
+fn save_or_exit(out: Option<PathBuf>, results: &[Tensor]) {
+    if let Some(path) = out {
+        if let Err(e) = save(&path, results) {
+            eprintln!("writing {}: {}", path.display(), e);
+            process::exit(1);
+        }
     }
 }
 
+// Without the npy feature --out is rejected, so there is never a file to save
+#[cfg(not(feature = "npy"))]
+fn save_or_exit(_: Option<PathBuf>, _: &[Tensor]) {}
+
 fn main() {
+    let options = match options() {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let out = options.out;
+
+    if let Some(path) = options.qasm {
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("reading {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        };
+
+        match simulate(&source, options.unitary) {
+            Ok((result, tensor)) => {
+                println!("{}", result);
+                save_or_exit(out, &[tensor]);
+            }
+            Err(e) => {
+                eprintln!("{}", locate(&path, &source, &e));
+                process::exit(1);
+            }
+        }
+
+        return;
+    }
+
     let stdin = io::stdin();
+    let mut results = Vec::new();
 
     for line in stdin.lock().lines() {
         match line {
             Err(e) => panic!("reading line: {:?}", e),
-            Ok(line_str) => match calculate(&line_str) {
-                Ok(tensor) => println!("{}", tensor),
-                Err(e) => println!("Cannot interpret `{}` as dirac notation: {}", line_str, e),
+            Ok(line_str) => match run(&line_str) {
+                Ok((result, tensor)) => {
+                    println!("{}", result);
+
+                    if out.is_some() {
+                        results.push(tensor);
+                    }
+                }
+                Err(e) => println!("{}", underline(&line_str, &e)),
             },
         }
     }
+
+    save_or_exit(out, &results);
 }
@@ -0,0 +1,213 @@
+//! Algebraic simplification of expressions ahead of evaluation.
+
+use crate::expression::{Expression, Kind, Span};
+use crate::tensor::AsTensor;
+use num::complex::Complex64;
+
+const ONE: Complex64 = Complex64::new(1.0, 0.0);
+
+impl Expression {
+    // Rewrites the expression bottom-up into an equivalent one that is cheaper
+    // to evaluate: parentheses are dropped, scalar subtrees are folded, inner
+    // products of basis states are computed in closed form, products of basis
+    // states are merged into a single label and scalar factors are pulled out
+    // of products so that they multiply the final tensor only once. Rewritten
+    // nodes keep the span of the node they replace.
+    pub fn optimize(self) -> Expression {
+        let optimize = |expr: Box<Expression>| Box::new((*expr).optimize());
+
+        let kind = match self.kind {
+            Kind::Parenthised(expr) => return (*expr).optimize(),
+            Kind::AdditiveInverse(expr) => Kind::AdditiveInverse(optimize(expr)),
+            Kind::Dagger(expr) => Kind::Dagger(optimize(expr)),
+            Kind::Norm(expr) => Kind::Norm(optimize(expr)),
+            Kind::Mul(a, b) => Kind::Mul(optimize(a), optimize(b)),
+            Kind::Div(a, b) => Kind::Div(optimize(a), optimize(b)),
+            Kind::Add(a, b) => Kind::Add(optimize(a), optimize(b)),
+            Kind::Sub(a, b) => Kind::Sub(optimize(a), optimize(b)),
+            Kind::Kronecker(a, b) => Kind::Kronecker(optimize(a), optimize(b)),
+            Kind::Pow(a, b) => Kind::Pow(optimize(a), optimize(b)),
+            Kind::Inner(a, b) => Kind::Inner(optimize(a), optimize(b)),
+            Kind::Commutator(a, b) => Kind::Commutator(optimize(a), optimize(b)),
+            Kind::Anticommutator(a, b) => Kind::Anticommutator(optimize(a), optimize(b)),
+            Kind::Function(function, args) => {
+                Kind::Function(function, args.into_iter().map(Self::optimize).collect())
+            }
+            Kind::Sum(variable, from, to, body) => Kind::Sum(variable, from, to, optimize(body)),
+            Kind::Let(variable, value, body) => Kind::Let(variable, value, optimize(body)),
+            Kind::LetState(name, value, body) => {
+                Kind::LetState(name, optimize(value), optimize(body))
+            }
+            leaf => leaf,
+        };
+
+        Expression::new(kind, self.span).simplify()
+    }
+
+    // Applies the rewrite rules at the root, assuming the children are
+    // already simplified
+    fn simplify(self) -> Expression {
+        let span = self.span;
+        let node = |kind| Expression::new(kind, span);
+
+        if self.has_scalar_operands() {
+            if let Some(c) = self.compute().ok().and_then(|tensor| tensor.item()) {
+                return node(Kind::Scalar(c));
+            }
+        }
+
+        match self.kind {
+            Kind::Constant(constant) => match constant.value().item() {
+                Some(c) => node(Kind::Scalar(c)),
+                None => node(Kind::Constant(constant)),
+            },
+            Kind::AdditiveInverse(expr) => scale(-ONE, *expr, span),
+            Kind::Dagger(expr) => {
+                let (c, expr) = expr.factor();
+
+                let dagger = match expr.kind {
+                    Kind::Ket(label) => Kind::Bra(label),
+                    Kind::Bra(label) => Kind::Ket(label),
+                    Kind::NamedKet(name) => Kind::NamedBra(name),
+                    Kind::NamedBra(name) => Kind::NamedKet(name),
+                    Kind::Outer(ket, bra) => Kind::Outer(bra, ket),
+                    kind => Kind::Dagger(Box::new(Expression::new(kind, expr.span))),
+                };
+
+                scale(c.conj(), node(dagger), span)
+            }
+            Kind::Mul(a, b) => {
+                let ((c, a), (d, b)) = (a.factor(), b.factor());
+
+                let product = match (a.kind, b.kind) {
+                    (Kind::Scalar(x), b_kind) => scale(x, Expression::new(b_kind, b.span), span),
+                    (a_kind, Kind::Scalar(y)) => scale(y, Expression::new(a_kind, a.span), span),
+                    (Kind::Ket(ket), Kind::Bra(bra)) => node(Kind::Outer(ket, bra)),
+                    (Kind::Bra(bra), Kind::Ket(ket)) if bra.len() == ket.len() => {
+                        node(Kind::Scalar(inner(&bra, &ket)))
+                    }
+                    (a_kind, b_kind) => node(Kind::Mul(
+                        Box::new(Expression::new(a_kind, a.span)),
+                        Box::new(Expression::new(b_kind, b.span)),
+                    )),
+                };
+
+                scale(c * d, product, span)
+            }
+            Kind::Kronecker(a, b) => {
+                let ((c, a), (d, b)) = (a.factor(), b.factor());
+
+                let product = match (a.kind, b.kind) {
+                    (Kind::Scalar(x), b_kind) => scale(x, Expression::new(b_kind, b.span), span),
+                    (a_kind, Kind::Scalar(y)) => scale(y, Expression::new(a_kind, a.span), span),
+                    (Kind::Ket(a), Kind::Ket(b)) => node(Kind::Ket(a + &b)),
+                    (Kind::Bra(a), Kind::Bra(b)) => node(Kind::Bra(a + &b)),
+                    (Kind::Outer(ket_a, bra_a), Kind::Outer(ket_b, bra_b)) => {
+                        node(Kind::Outer(ket_a + &ket_b, bra_a + &bra_b))
+                    }
+                    (a_kind, b_kind) => node(Kind::Kronecker(
+                        Box::new(Expression::new(a_kind, a.span)),
+                        Box::new(Expression::new(b_kind, b.span)),
+                    )),
+                };
+
+                scale(c * d, product, span)
+            }
+            Kind::Div(a, b) => match b.kind {
+                Kind::Scalar(y) if y != 0.0.into() => scale(1.0 / y, *a, span),
+                b_kind => {
+                    let (c, a) = a.factor();
+                    let b = Expression::new(b_kind, b.span);
+
+                    scale(c, node(Kind::Div(Box::new(a), Box::new(b))), span)
+                }
+            },
+            Kind::Inner(a, b) => {
+                let ((c, a), (d, b)) = (a.factor(), b.factor());
+
+                let product = match (a.kind, b.kind) {
+                    (Kind::Bra(bra), Kind::Ket(ket)) if bra.len() == ket.len() => {
+                        node(Kind::Scalar(inner(&bra, &ket)))
+                    }
+                    (a_kind, b_kind) => node(Kind::Inner(
+                        Box::new(Expression::new(a_kind, a.span)),
+                        Box::new(Expression::new(b_kind, b.span)),
+                    )),
+                };
+
+                scale(c * d, product, span)
+            }
+            kind => node(kind),
+        }
+    }
+
+    // Whether all operands of an operation are scalars, so that it can be
+    // folded into a single scalar. Sums and bindings are left alone as their
+    // bounds may depend on enclosing variables.
+    fn has_scalar_operands(&self) -> bool {
+        let scalar = |expr: &Expression| matches!(expr.kind, Kind::Scalar(_));
+
+        match &self.kind {
+            Kind::AdditiveInverse(a) | Kind::Dagger(a) | Kind::Norm(a) => scalar(a),
+            Kind::Mul(a, b)
+            | Kind::Div(a, b)
+            | Kind::Add(a, b)
+            | Kind::Sub(a, b)
+            | Kind::Kronecker(a, b)
+            | Kind::Pow(a, b)
+            | Kind::Inner(a, b)
+            | Kind::Commutator(a, b)
+            | Kind::Anticommutator(a, b) => scalar(a) && scalar(b),
+            Kind::Function(_, args) => args.iter().all(scalar),
+            _ => false,
+        }
+    }
+
+    // Splits a simplified expression into its scalar factor and the rest
+    fn factor(self) -> (Complex64, Expression) {
+        match self.kind {
+            Kind::Mul(a, b) => match a.kind {
+                Kind::Scalar(c) => (c, *b),
+                a_kind => (
+                    ONE,
+                    Expression::new(
+                        Kind::Mul(Box::new(Expression::new(a_kind, a.span)), b),
+                        self.span,
+                    ),
+                ),
+            },
+            kind => (ONE, Expression::new(kind, self.span)),
+        }
+    }
+}
+
+// Multiplies a simplified expression by a scalar, merging it with the
+// expression's own scalar factor. The result spans the given source range.
+fn scale(c: Complex64, expr: Expression, span: Span) -> Expression {
+    match expr.factor() {
+        (
+            d,
+            Expression {
+                kind: Kind::Scalar(x),
+                ..
+            },
+        ) => Expression::new(Kind::Scalar(c * d * x), span),
+        (d, expr) if c * d == ONE => Expression::new(expr.kind, span),
+        (d, expr) => Expression::new(
+            Kind::Mul(
+                Box::new(Expression::new(Kind::Scalar(c * d), span)),
+                Box::new(expr),
+            ),
+            span,
+        ),
+    }
+}
+
+// Inner product <bra|ket> of two basis states of the same width, as the
+// product of the inner products of their qubits
+fn inner(bra: &str, ket: &str) -> Complex64 {
+    bra.chars()
+        .zip(ket.chars())
+        .map(|(a, b)| a.as_tensor().dag() | b.as_tensor())
+        .product()
+}
@@ -1,93 +1,414 @@
-use super::expression::Expression;
-use crate::tensor::{AsTensor, KroneckerProduct, Tensor};
+use super::builtins::{Constant, Function};
+use super::error::Error;
+use super::expression::{Expression, Index, Kind, Span};
+use crate::tensor::Pauli;
 use nom::branch::alt;
-use nom::bytes::complete::take_while1;
-use nom::character::complete::char;
-use nom::combinator::{all_consuming, opt};
-use nom::multi::many0;
-use nom::sequence::Tuple;
-use nom::IResult;
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::{char, digit1, satisfy};
+use nom::combinator::{map, opt, recognize};
+use nom::error::{ErrorKind, ParseError};
+use nom::multi::{many0, many1, separated_list1};
+use nom::sequence::{delimited, preceded, Tuple};
+use nom_locate::LocatedSpan;
 use num::complex::Complex64;
 use whitespace::ws;
 
 mod whitespace;
 
-// Builds a tensor from a sequence of 01+- characters by applying a sequence of
-// Kronecker products.
-fn tensor_basis(basis: &str) -> Tensor {
-    basis
-        .chars()
-        .map(|c| c.as_tensor())
-        .collect::<Vec<Tensor>>()
-        .prod()
+// Parser input, tracking the byte offset of each position in the source
+pub(crate) type Input<'a> = LocatedSpan<&'a str>;
+
+pub(crate) type IResult<'a, O> = nom::IResult<Input<'a>, O, SyntaxError<'a>>;
+
+// Parse error that keeps, out of failed alternatives, the one that got
+// furthest into the input, so that errors point past the longest valid prefix
+// rather than at the start of the last alternative tried
+#[derive(Debug)]
+pub(crate) struct SyntaxError<'a> {
+    input: Input<'a>,
+    // Known cause of the error, such as a malformed number
+    reason: Option<Error>,
+}
+
+impl<'a> SyntaxError<'a> {
+    pub(crate) fn new(input: Input<'a>, reason: Error) -> SyntaxError<'a> {
+        SyntaxError {
+            input,
+            reason: Some(reason),
+        }
+    }
+}
+
+impl<'a> ParseError<Input<'a>> for SyntaxError<'a> {
+    fn from_error_kind(input: Input<'a>, _: ErrorKind) -> Self {
+        SyntaxError {
+            input,
+            reason: None,
+        }
+    }
+
+    fn append(_: Input<'a>, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(self, other: Self) -> Self {
+        match self.input.location_offset() > other.input.location_offset() {
+            true => self,
+            false => other,
+        }
+    }
+}
+
+// Span of the input consumed by a parser, leaving out surrounding whitespace
+pub(crate) fn span(input: Input, rem: Input) -> Span {
+    let consumed = &input.fragment()[..rem.location_offset() - input.location_offset()];
+    let start = input.location_offset() + consumed.len() - consumed.trim_start().len();
+
+    Span::new(start, start + consumed.trim().len())
+}
+
+// Reports the first character of the input that could not be parsed
+fn unexpected(rem: Input) -> Error {
+    let rest = rem.fragment().trim_start();
+    let start = rem.location_offset() + rem.fragment().len() - rest.len();
+
+    match rest.chars().next() {
+        Some(c) => Error::new(
+            format!("unexpected `{}`", c),
+            Span::new(start, start + c.len_utf8()),
+        ),
+        None => Error::new("unexpected end of input", Span::new(start, start)),
+    }
 }
 
 // Matches a string composed of 01+- representing a basis for a quantum state
-fn basis(input: &str) -> IResult<&str, &str> {
+fn basis(input: Input) -> IResult<Input> {
     ws(take_while1(|c: char| "01+-".contains(c)))(input)
 }
 
-// Matches a ket |ket>
-fn ket(input: &str) -> IResult<&str, Expression> {
-    let (rem, (_, ket_str, _)) = (char('|'), basis, char('>')).parse(input)?;
+// Matches the angle brackets of bras and kets, either in ASCII or as the
+// mathematical ⟨ and ⟩
+fn left_angle(input: Input) -> IResult<char> {
+    alt((char('<'), char('⟨')))(input)
+}
 
-    Ok((rem, Expression::Ket(tensor_basis(ket_str))))
+fn right_angle(input: Input) -> IResult<char> {
+    alt((char('>'), char('⟩')))(input)
 }
 
-// Matches a bra <bra|
-fn bra(input: &str) -> IResult<&str, Expression> {
-    let (rem, (_, bra_str, _)) = (char('<'), basis, char('|')).parse(input)?;
+// Label of a bra or ket
+enum Label {
+    // Basis state made of 01+-
+    Basis(String),
+    // State bound by ψ = expr;
+    Name(String),
+}
 
-    Ok((rem, Expression::Bra(tensor_basis(bra_str))))
+impl Label {
+    fn ket(self) -> Kind {
+        match self {
+            Self::Basis(label) => Kind::Ket(label),
+            Self::Name(name) => Kind::NamedKet(name),
+        }
+    }
+
+    fn bra(self) -> Kind {
+        match self {
+            Self::Basis(label) => Kind::Bra(label),
+            Self::Name(name) => Kind::NamedBra(name),
+        }
+    }
+}
+
+// Matches the label of a bra or ket, either a basis state or a name
+fn label(input: Input) -> IResult<Label> {
+    alt((
+        map(basis, |basis| Label::Basis(basis.to_string())),
+        map(ws(identifier), |name| Label::Name(name.to_string())),
+    ))(input)
+}
+
+// Matches a ket |ket> or |ket⟩, such as |01> or |ψ⟩
+fn ket(input: Input) -> IResult<Expression> {
+    let (rem, (_, label, _)) = (char('|'), label, right_angle).parse(input)?;
+
+    Ok((rem, Expression::new(label.ket(), span(input, rem))))
+}
+
+// Matches a bra <bra| or ⟨bra|, such as <01| or ⟨ψ|
+fn bra(input: Input) -> IResult<Expression> {
+    let (rem, (_, label, _)) = (left_angle, label, char('|')).parse(input)?;
+
+    Ok((rem, Expression::new(label.bra(), span(input, rem))))
 }
 
 // Matches ehter the real or imaginary part of a complex number
-fn number(input: &str) -> IResult<&str, Expression> {
+fn number(input: Input) -> IResult<Expression> {
     let (rem, num_str) = take_while1(|c: char| c.is_numeric() || c == '.' || c == 'i')(input)?;
 
-    // NOTE: We are just unwraping the result of the string conversion so any
-    // syntax errors while typing scalars are going to manifest themselves as
-    // panics.
-
-    match num_str.strip_suffix('i') {
+    let value = match num_str.fragment().strip_suffix('i') {
         // We are dealing with the imaginary part of a complex number...
-        Some(im_str) => match im_str {
-            "" => Ok((rem, Expression::Scalar(Complex64::new(0.0, 1.0)))),
-            _ => Ok((
-                rem,
-                Expression::Scalar(Complex64::new(0.0, im_str.parse().unwrap())),
-            )),
-        },
+        Some("") => Ok(Complex64::new(0.0, 1.0)),
+        Some(im_str) => im_str.parse().map(|im| Complex64::new(0.0, im)),
         // ...and with the real part
-        None => Ok((
+        None => num_str.fragment().parse().map(|re| Complex64::new(re, 0.0)),
+    };
+
+    match value {
+        Ok(c) => Ok((rem, Expression::new(Kind::Scalar(c), span(input, rem)))),
+        // Nothing else starts with these characters, so there is no
+        // alternative left to try
+        Err(_) => Err(nom::Err::Failure(SyntaxError::new(
+            input,
+            Error::new(
+                format!("invalid number `{}`", num_str.fragment()),
+                span(input, rem),
+            ),
+        ))),
+    }
+}
+
+// Matches a name starting with a letter, followed by letters, digits or _
+pub(crate) fn identifier(input: Input) -> IResult<Input> {
+    recognize(|input| {
+        (
+            satisfy(|c| c.is_alphabetic()),
+            take_while(|c: char| c.is_alphanumeric() || c == '_'),
+        )
+            .parse(input)
+    })(input)
+}
+
+// Matches a named constant such as pi or e
+fn constant(input: Input) -> IResult<Expression> {
+    let (rem, name) = identifier(input)?;
+
+    match Constant::from_name(name.fragment()) {
+        Some(constant) => Ok((
+            rem,
+            Expression::new(Kind::Constant(constant), span(input, rem)),
+        )),
+        None => Err(nom::Err::Error(SyntaxError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        ))),
+    }
+}
+
+// Matches a function call name(expr, ...)
+fn call(input: Input) -> IResult<Expression> {
+    let (rem, (name, _, args, _)) = (
+        identifier,
+        ws(char('(')),
+        separated_list1(char(','), additive),
+        char(')'),
+    )
+        .parse(input)?;
+
+    match Function::from_name(name.fragment()) {
+        Some(function) if function.arity() == args.len() => Ok((
             rem,
-            Expression::Scalar(Complex64::new(num_str.parse().unwrap(), 0.0)),
+            Expression::new(Kind::Function(function, args), span(input, rem)),
         )),
+        // Other names may still be a constant or scalar followed by a
+        // parenthised factor, as in pi(2) or i(|0> + |1>)
+        None => Err(nom::Err::Error(SyntaxError::from_error_kind(
+            input,
+            ErrorKind::Verify,
+        ))),
+        Some(function) => Err(nom::Err::Failure(SyntaxError::new(
+            input,
+            Error::new(
+                format!(
+                    "`{}` takes {} argument(s) but {} were given",
+                    name.fragment(),
+                    function.arity(),
+                    args.len()
+                ),
+                span(input, rem),
+            ),
+        ))),
+    }
+}
+
+// Matches a non-negative integer literal
+fn integer(input: Input) -> IResult<Index> {
+    let (rem, digits) = digit1(input)?;
+
+    match digits.fragment().parse() {
+        Ok(n) => Ok((rem, Index::Number(n))),
+        Err(_) => Err(nom::Err::Failure(SyntaxError::new(
+            input,
+            Error::new(
+                format!("integer `{}` is too large", digits.fragment()),
+                span(input, rem),
+            ),
+        ))),
     }
 }
 
-// Matches a bra-ket inner product <bra|ket>
-fn inner(input: &str) -> IResult<&str, Expression> {
-    let (rem, (_, bra_str, _, ket_str, _)) =
-        (char('<'), basis, char('|'), basis, char('>')).parse(input)?;
+// Matches an integer literal, variable or parenthised index
+fn index_atom(input: Input) -> IResult<Index> {
+    let variable = |input| {
+        let (rem, name) = identifier(input)?;
+
+        Ok((rem, Index::Variable(name.to_string())))
+    };
+
+    ws(alt((
+        integer,
+        variable,
+        delimited(char('('), index, char(')')),
+    )))(input)
+}
+
+// Matches products of index atoms
+fn index_term(input: Input) -> IResult<Index> {
+    let (rem, (first, rest)) = (index_atom, many0(preceded(char('*'), index_atom))).parse(input)?;
+
+    Ok((
+        rem,
+        rest.into_iter().fold(first, |acc, index| {
+            Index::Mul(Box::new(acc), Box::new(index))
+        }),
+    ))
+}
+
+// Matches an integer expression used as a qubit index or summation bound,
+// with +, - and * over integers and variables: n-2, i+1, 2*i
+fn index(input: Input) -> IResult<Index> {
+    let operation = |input| (alt((char('+'), char('-'))), index_term).parse(input);
+    let (rem, (first, rest)) = (index_term, many0(operation)).parse(input)?;
+
+    Ok((
+        rem,
+        rest.into_iter().fold(first, |acc, (op, index)| match op {
+            '+' => Index::Add(Box::new(acc), Box::new(index)),
+            '-' => Index::Sub(Box::new(acc), Box::new(index)),
+            _ => unreachable!("should only ever match +, -"),
+        }),
+    ))
+}
+
+// Matches an index in braces {index} or a single index atom
+fn braced_index(input: Input) -> IResult<Index> {
+    alt((delimited(ws(char('{')), index, char('}')), index_atom))(input)
+}
+
+// Matches a Pauli operator on a qubit: X0, Z12, Y_i or Z_{i+1}
+fn pauli(input: Input) -> IResult<(Pauli, Index)> {
+    let (rem, (op, site)) = (
+        satisfy(|c| "IXYZ".contains(c)),
+        alt((integer, preceded(ws(char('_')), braced_index))),
+    )
+        .parse(input)?;
+
+    Ok((rem, (Pauli::from_char(op).unwrap(), site)))
+}
+
+// Matches a product of Pauli operators: Z0 Z1 X3
+fn pauli_string(input: Input) -> IResult<Expression> {
+    let (rem, factors) = many1(ws(pauli))(input)?;
+
+    Ok((rem, Expression::new(Kind::Pauli(factors), span(input, rem))))
+}
+
+// Matches a sum over an integer range sum_{i=from}^{to} expr, where expr is a
+// multiplicative expression: sum_{i=0}^{n-2} Z_i Z_{i+1}
+fn sum(input: Input) -> IResult<Expression> {
+    let (rem, (_, _, _, variable, _, from, _, _, to, body)) = (
+        tag("sum"),
+        ws(char('_')),
+        ws(char('{')),
+        ws(identifier),
+        char('='),
+        index,
+        char('}'),
+        ws(char('^')),
+        braced_index,
+        ws(multiplicative),
+    )
+        .parse(input)?;
 
     Ok((
         rem,
-        Expression::Inner(
-            Box::new(Expression::Bra(tensor_basis(bra_str))),
-            Box::new(Expression::Ket(tensor_basis(ket_str))),
+        Expression::new(
+            Kind::Sum(variable.to_string(), from, to, Box::new(body)),
+            span(input, rem),
         ),
     ))
 }
 
-// Matches a bra-ket outer product |ket><bra|
-fn outer(input: &str) -> IResult<&str, Expression> {
-    let (rem, ketbra) = (ket, bra).parse(input)?;
+// Value of a binding
+enum Binding {
+    // Integer variable, as in n = 4;
+    Index(Index),
+    // Named ket, as in ψ = (|0> + |1>)/√2;
+    State(Expression),
+}
 
-    match ketbra {
-        (Expression::Ket(ket), Expression::Bra(bra)) => Ok((rem, Expression::Outer(ket, bra))),
-        _ => unreachable!("ket and bra must return a ket and a bra"),
-    }
+// Matches a binding name = value; of an integer variable, or else of a state
+fn binding(input: Input) -> IResult<(String, Binding, Span)> {
+    let (rem, (name, _, value)) = (
+        ws(identifier),
+        char('='),
+        alt((
+            map(
+                |input| (index, char(';')).parse(input),
+                |(index, _)| Binding::Index(index),
+            ),
+            map(
+                |input| (additive, char(';')).parse(input),
+                |(state, _)| Binding::State(state),
+            ),
+        )),
+    )
+        .parse(input)?;
+
+    Ok((rem, (name.to_string(), value, span(input, rem))))
+}
+
+// Matches a bra-ket inner product <bra|ket>, whose bra and ket share the
+// middle bar
+fn inner(input: Input) -> IResult<Expression> {
+    let (ket_input, bra) = bra(input)?;
+    let (rem, (label, _)) = (label, right_angle).parse(ket_input)?;
+    let ket = Expression::new(
+        label.ket(),
+        Span::new(ket_input.location_offset() - 1, rem.location_offset()),
+    );
+
+    Ok((
+        rem,
+        Expression::new(Kind::Inner(Box::new(bra), Box::new(ket)), span(input, rem)),
+    ))
+}
+
+// Matches a bra-ket outer product |ket><bra|, which is a product when either
+// side is a named state
+fn outer(input: Input) -> IResult<Expression> {
+    let (rem, (ket, bra)) = (ket, bra).parse(input)?;
+
+    let kind = match (ket.kind, bra.kind) {
+        (Kind::Ket(ket), Kind::Bra(bra)) => Kind::Outer(ket, bra),
+        (ket_kind, bra_kind) => Kind::Mul(
+            Box::new(Expression::new(ket_kind, ket.span)),
+            Box::new(Expression::new(bra_kind, bra.span)),
+        ),
+    };
+
+    Ok((rem, Expression::new(kind, span(input, rem))))
+}
+
+// Matches a square root written with the radical sign, √2 or √(1 + i), which
+// applies to the atom that follows it
+fn root(input: Input) -> IResult<Expression> {
+    let (rem, (_, arg)) = (char('√'), atom).parse(input)?;
+
+    Ok((
+        rem,
+        Expression::new(Kind::Function(Function::Sqrt, vec![arg]), span(input, rem)),
+    ))
 }
 
 // Matches one of:
@@ -98,8 +419,20 @@ fn outer(input: &str) -> IResult<&str, Expression> {
 // - ket
 // - parenthised expression
 // - normalzied expression
-fn atom(input: &str) -> IResult<&str, Expression> {
+// - function call
+// - square root
+// - named constant
+// - Pauli string
+// - sum
+// - commutator
+// - anticommutator
+fn atom(input: Input) -> IResult<Expression> {
     alt((
+        ws(sum),
+        ws(pauli_string),
+        ws(call),
+        ws(root),
+        ws(constant),
         ws(number),
         ws(outer),
         ws(inner),
@@ -107,41 +440,78 @@ fn atom(input: &str) -> IResult<&str, Expression> {
         ws(ket),
         ws(parenthised),
         ws(norm),
+        ws(commutator),
+        ws(anticommutator),
     ))(input)
 }
 
-// Matches a transpose conjugate operation in the form expr'
-fn dag(input: &str) -> IResult<&str, Expression> {
-    let (rem, out) = (atom, opt(char('\''))).parse(input)?;
+// Matches a transpose conjugate operation in the form expr' or expr†
+fn dag(input: Input) -> IResult<Expression> {
+    let (rem, out) = (atom, opt(alt((char('\''), char('†'))))).parse(input)?;
 
     match out {
-        (expr, Some(_)) => Ok((rem, Expression::Dagger(Box::new(expr)))),
+        (expr, Some(_)) => Ok((
+            rem,
+            Expression::new(Kind::Dagger(Box::new(expr)), span(input, rem)),
+        )),
         (expr, None) => Ok((rem, expr)),
     }
 }
 
+// Matches an exponentiation expr^expr, which binds tighter than the additive
+// inverse and is right associative: -2^-2^2 is -(2^(-(2^2)))
+fn power(input: Input) -> IResult<Expression> {
+    let (rem, (base, exponent)) = (dag, opt(preceded(ws(char('^')), inverse))).parse(input)?;
+
+    match exponent {
+        Some(exponent) => {
+            let span = base.span.join(exponent.span);
+
+            Ok((
+                rem,
+                Expression::new(Kind::Pow(Box::new(base), Box::new(exponent)), span),
+            ))
+        }
+        None => Ok((rem, base)),
+    }
+}
+
 // Matches the additive inverse of some expression, or the expression itself: expr or -expr
-fn inverse(input: &str) -> IResult<&str, Expression> {
-    let (rem, (inverse, expr)) = (opt(char('-')), ws(dag)).parse(input)?;
+fn inverse(input: Input) -> IResult<Expression> {
+    let (rem, (inverse, expr)) = (opt(char('-')), ws(power)).parse(input)?;
 
     match (inverse, expr) {
-        (Some(_), expr) => Ok((rem, Expression::AdditiveInverse(Box::new(expr)))),
+        (Some(_), expr) => Ok((
+            rem,
+            Expression::new(Kind::AdditiveInverse(Box::new(expr)), span(input, rem)),
+        )),
         (None, expr) => Ok((rem, expr)),
     }
 }
 
-// Matches a multiplicative operation expr op expr, where op is one of *, /, x, .
-// x represents the Kronecker product.
+// Matches a multiplicative operation expr op expr, where op is one of *, ·, /,
+// x, ⊗, .
+// x and ⊗ represent the Kronecker product.
 // . represents the dot (inner) product.
-fn multiplicative(input: &str) -> IResult<&str, Expression> {
+fn multiplicative(input: Input) -> IResult<Expression> {
     let operation = |input| {
-        let (rem, (char, expr)) =
-            (alt((char('*'), char('/'), char('x'), char('.'))), inverse).parse(input)?;
+        let (rem, (char, expr)) = (
+            alt((
+                char('*'),
+                char('·'),
+                char('/'),
+                char('x'),
+                char('⊗'),
+                char('.'),
+            )),
+            inverse,
+        )
+            .parse(input)?;
 
         Ok((rem, (Some(char), expr)))
     };
     let direct = |input| {
-        let (rem, expr) = ws(dag)(input)?;
+        let (rem, expr) = ws(power)(input)?;
 
         Ok((rem, (None, expr)))
     };
@@ -149,74 +519,170 @@ fn multiplicative(input: &str) -> IResult<&str, Expression> {
 
     // Pass-through case: there are no operations so we just return the first
     // expression
-    if rest.len() == 0 {
+    if rest.is_empty() {
         return Ok((rem, first));
     }
 
     // Accumulator
     let mut acc = first;
 
-    for operation in rest {
-        match operation {
-            (Some('*'), expr) => acc = Expression::Mul(Box::new(acc), Box::new(expr)),
-            (Some('/'), expr) => acc = Expression::Div(Box::new(acc), Box::new(expr)),
-            (Some('x'), expr) => acc = Expression::Kronecker(Box::new(acc), Box::new(expr)),
-            (Some('.'), expr) => acc = Expression::Inner(Box::new(acc), Box::new(expr)),
-            (Some(_), _) => unreachable!("should only ever match *, /, x, ."),
-            (None, expr) => acc = Expression::Mul(Box::new(acc), Box::new(expr)),
-        }
+    for (op, expr) in rest {
+        let span = acc.span.join(expr.span);
+        let (a, b) = (Box::new(acc), Box::new(expr));
+
+        let kind = match op {
+            Some('*') | Some('·') | None => Kind::Mul(a, b),
+            Some('/') => Kind::Div(a, b),
+            Some('x') | Some('⊗') => Kind::Kronecker(a, b),
+            Some('.') => Kind::Inner(a, b),
+            Some(_) => unreachable!("should only ever match *, ·, /, x, ⊗, ."),
+        };
+
+        acc = Expression::new(kind, span);
     }
 
     Ok((rem, acc))
 }
 
 // Matches additive expressions, sum or subtraction
-fn additive(input: &str) -> IResult<&str, Expression> {
+fn additive(input: Input) -> IResult<Expression> {
     let operation = |input| (alt((char('+'), char('-'))), multiplicative).parse(input);
     let (rem, (first, rest)) = (multiplicative, many0(operation)).parse(input)?;
 
     // Pass-through case: there are no operations so we just return the first
     // expression
-    if rest.len() == 0 {
+    if rest.is_empty() {
         return Ok((rem, first));
     }
 
     // Accumulator
     let mut acc = first;
 
-    for operation in rest {
-        match operation {
-            ('+', expr) => acc = Expression::Add(Box::new(acc), Box::new(expr)),
-            ('-', expr) => acc = Expression::Sub(Box::new(acc), Box::new(expr)),
-            (_, _) => unreachable!("should only ever match +, -"),
-        }
+    for (op, expr) in rest {
+        let span = acc.span.join(expr.span);
+        let (a, b) = (Box::new(acc), Box::new(expr));
+
+        let kind = match op {
+            '+' => Kind::Add(a, b),
+            '-' => Kind::Sub(a, b),
+            _ => unreachable!("should only ever match +, -"),
+        };
+
+        acc = Expression::new(kind, span);
     }
 
     Ok((rem, acc))
 }
 
 // Matches a parenthised expression ( expr )
-fn parenthised(input: &str) -> IResult<&str, Expression> {
+fn parenthised(input: Input) -> IResult<Expression> {
     let (rem, (_, expr, _)) = (char('('), additive, char(')')).parse(input)?;
 
-    Ok((rem, Expression::Parenthised(Box::new(expr))))
+    Ok((
+        rem,
+        Expression::new(Kind::Parenthised(Box::new(expr)), span(input, rem)),
+    ))
+}
+
+// Matches a commutator [ expr, expr ]
+fn commutator(input: Input) -> IResult<Expression> {
+    let (rem, (_, a, _, b, _)) =
+        (char('['), additive, char(','), additive, char(']')).parse(input)?;
+
+    Ok((
+        rem,
+        Expression::new(Kind::Commutator(Box::new(a), Box::new(b)), span(input, rem)),
+    ))
+}
+
+// Matches an anticommutator { expr, expr }
+fn anticommutator(input: Input) -> IResult<Expression> {
+    let (rem, (_, a, _, b, _)) =
+        (char('{'), additive, char(','), additive, char('}')).parse(input)?;
+
+    Ok((
+        rem,
+        Expression::new(
+            Kind::Anticommutator(Box::new(a), Box::new(b)),
+            span(input, rem),
+        ),
+    ))
 }
 
 // Matches a normalized expression | expr |
-fn norm(input: &str) -> IResult<&str, Expression> {
+fn norm(input: Input) -> IResult<Expression> {
     let (rem, (_, expr, _)) = (char('|'), additive, char('|')).parse(input)?;
 
-    Ok((rem, Expression::Norm(Box::new(expr))))
+    Ok((
+        rem,
+        Expression::new(Kind::Norm(Box::new(expr)), span(input, rem)),
+    ))
+}
+
+// Parses a dirac notation expression, optionally preceded by variable
+// bindings and followed by `, normalize`, which scales the result to unit
+// norm: n = 4; sum_{i=0}^{n-1} X_i |0000>, normalize. Every node of the
+// resulting tree records the byte range of the input it was parsed from, and
+// so do errors.
+pub fn dirac(input: &str) -> Result<Expression, Error> {
+    let input = Input::new(input);
+    let (bindings, expr, normalize) = complete(
+        (
+            many0(binding),
+            additive,
+            opt(preceded(ws(char(',')), ws(tag("normalize")))),
+        )
+            .parse(input),
+    )?;
+
+    let expr = match normalize {
+        Some(tag) => {
+            let end = tag.location_offset() + tag.fragment().len();
+            let span = Span::new(expr.span.start, end);
+
+            Expression::new(Kind::Function(Function::Normalize, vec![expr]), span)
+        }
+        None => expr,
+    };
+
+    Ok(bind(bindings, expr))
+}
+
+// Result of a parser that should have consumed the whole input, or the error
+// to report
+pub(crate) fn complete<O>(result: IResult<O>) -> Result<O, Error> {
+    match result {
+        Ok((rem, output)) if rem.fragment().is_empty() => Ok(output),
+        Ok((rem, _)) => Err(unexpected(rem)),
+        Err(nom::Err::Error(error) | nom::Err::Failure(error)) => match error.reason {
+            Some(reason) => Err(reason),
+            None => Err(unexpected(error.input)),
+        },
+        Err(nom::Err::Incomplete(_)) => unreachable!("complete parsers never need more input"),
+    }
 }
 
-// Matches a dirac notation expression
-pub fn dirac(input: &str) -> IResult<&str, Expression> {
-    all_consuming(additive)(input)
+// Wraps an expression in its bindings, the first binding outermost
+fn bind(bindings: Vec<(String, Binding, Span)>, expr: Expression) -> Expression {
+    bindings
+        .into_iter()
+        .rev()
+        .fold(expr, |body, (name, value, span)| {
+            let span = span.join(body.span);
+            let kind = match value {
+                Binding::Index(value) => Kind::Let(name, value, Box::new(body)),
+                Binding::State(value) => Kind::LetState(name, Box::new(value), Box::new(body)),
+            };
+
+            Expression::new(kind, span)
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::dirac;
+    use crate::builtins::Function;
+    use crate::expression::{Kind, Span};
 
     #[test]
     fn spaced() {
@@ -225,7 +691,7 @@ mod tests {
 
     #[test]
     fn debug() {
-        let (_, expression) = dirac("<1| . |0> x |1> * (8 + 3i)").unwrap();
+        let expression = dirac("<1| . |0> x |1> * (8 + 3i)").unwrap();
         dbg!(expression);
     }
 
@@ -319,8 +785,182 @@ mod tests {
         assert!(dirac("|0>-").is_err());
     }
 
+    #[test]
+    fn power() {
+        assert!(dirac("2^2").is_ok());
+        assert!(dirac("-2^-2").is_ok());
+        assert!(dirac("(|0><1| + |1><0|)^2").is_ok());
+        assert!(dirac("2 ^ 3 ^ 2").is_ok());
+
+        assert!(dirac("2^").is_err());
+    }
+
+    #[test]
+    fn functions() {
+        assert!(dirac("sqrt(2)").is_ok());
+        assert!(dirac("1/sqrt(2) (|0> + |1>)").is_ok());
+        assert!(dirac("exp(i*pi/4)").is_ok());
+        assert!(dirac("im(3 + 2i) * re(1i)").is_ok());
+        assert!(dirac("abs(conj(e))").is_ok());
+
+        assert!(dirac("sqrt").is_err());
+        assert!(dirac("sqrt()").is_err());
+        assert!(dirac("sqrt(1, 2)").is_err());
+        assert!(dirac("unknown(1)").is_err());
+    }
+
+    #[test]
+    fn pauli() {
+        assert!(dirac("Z0").is_ok());
+        assert!(dirac("Z0 Z1 + 0.5 X2").is_ok());
+        assert!(dirac("Z0Z1").is_ok());
+        assert!(dirac("Z_0 Y_{1} X_{ 2 }").is_ok());
+        assert!(dirac("X0 |00>").is_ok());
+
+        assert!(dirac("Z_").is_err());
+        assert!(dirac("Z_{}").is_err());
+    }
+
+    #[test]
+    fn sum() {
+        assert!(dirac("sum_{i=0}^{3} Z_i").is_ok());
+        assert!(dirac("sum_{i=0}^3 Z_i Z_{i+1} + X0").is_ok());
+        assert!(dirac("n = 4; sum_{i=0}^{n-2} Z_i Z_{i+1}").is_ok());
+        assert!(dirac("sum_ { i = 0 } ^ { n - 2 } Z_i Z_ { i + 1 }").is_ok());
+        assert!(dirac("n = 4; m = n * 2; sum_{i=0}^{m-1} -0.5 X_i").is_ok());
+
+        assert!(dirac("sum_{i}^{3} Z_i").is_err());
+        assert!(dirac("n = 4 sum_{i=0}^{n} Z_i").is_err());
+    }
+
+    #[test]
+    fn commutators() {
+        assert!(dirac("[X, Y]").is_ok());
+        assert!(dirac("{X, Y}").is_ok());
+        assert!(dirac("[X x I, {Z0, X1}] + 2 [ |0><1| , |1><0| ]").is_ok());
+        assert!(dirac("{ [X, Y], Z }").is_ok());
+        assert!(dirac("sum_{i=0}^{1} {X_i, Z_{i+1}}").is_ok());
+
+        assert!(dirac("[X]").is_err());
+        assert!(dirac("[X, Y, Z]").is_err());
+        assert!(dirac("{X, Y]").is_err());
+        assert!(dirac("(X, Y)").is_err());
+    }
+
+    #[test]
+    fn normalize() {
+        assert!(dirac("normalize(|0> + |1>)").is_ok());
+        assert!(dirac("|0> + |1>, normalize").is_ok());
+        assert!(dirac("n = 1; X_n |00> , normalize").is_ok());
+
+        assert!(dirac("|0>, normalize, normalize").is_err());
+        assert!(dirac("|0>, unit").is_err());
+        assert!(dirac("normalize(|0>, |1>)").is_err());
+    }
+
     #[test]
     fn mixed() {
         assert!(dirac("|0>+|0>-|1>/|1>").is_ok());
     }
+
+    #[test]
+    fn spans() {
+        let expression = dirac(" |0> + 2 X'").unwrap();
+        assert_eq!(expression.span, Span::new(1, 11));
+
+        match expression.kind {
+            Kind::Add(a, b) => {
+                assert_eq!(a.span, Span::new(1, 4));
+                assert_eq!(b.span, Span::new(7, 11));
+
+                match b.kind {
+                    Kind::Mul(c, x) => {
+                        assert_eq!(c.span, Span::new(7, 8));
+                        assert_eq!(x.span, Span::new(9, 11));
+                    }
+                    kind => panic!("expected a product, got {:?}", kind),
+                }
+            }
+            kind => panic!("expected a sum, got {:?}", kind),
+        }
+
+        match dirac("<0|1>").unwrap().kind {
+            Kind::Inner(bra, ket) => {
+                assert_eq!(bra.span, Span::new(0, 3));
+                assert_eq!(ket.span, Span::new(2, 5));
+            }
+            kind => panic!("expected an inner product, got {:?}", kind),
+        }
+
+        let expression = dirac("n = 2; Z_n, normalize").unwrap();
+        assert_eq!(expression.span, Span::new(0, 21));
+        assert!(matches!(expression.kind, Kind::Let(..)));
+    }
+
+    #[test]
+    fn unicode() {
+        assert!(matches!(
+            dirac("|0⟩ ⊗ |1⟩").unwrap().kind,
+            Kind::Kronecker(..)
+        ));
+        assert!(matches!(dirac("X · Y").unwrap().kind, Kind::Mul(..)));
+        assert!(matches!(dirac("X†").unwrap().kind, Kind::Dagger(_)));
+        assert!(matches!(dirac("⟨0|").unwrap().kind, Kind::Bra(_)));
+        assert!(matches!(dirac("π").unwrap().kind, Kind::Constant(_)));
+
+        // Spans are byte offsets, and ⟨ and ⟩ take three bytes
+        match dirac("⟨0|1⟩").unwrap().kind {
+            Kind::Inner(bra, ket) => {
+                assert_eq!(bra.span, Span::new(0, 5));
+                assert_eq!(ket.span, Span::new(4, 9));
+            }
+            kind => panic!("expected an inner product, got {:?}", kind),
+        }
+
+        match dirac("√2 X").unwrap().kind {
+            Kind::Mul(root, _) => {
+                assert!(matches!(root.kind, Kind::Function(Function::Sqrt, _)));
+                assert_eq!(root.span, Span::new(0, 4));
+            }
+            kind => panic!("expected a product, got {:?}", kind),
+        }
+
+        let error = dirac("|0⟩ ⊗ ⊗").unwrap_err();
+        assert_eq!(error.message, "unexpected `⊗`");
+        assert_eq!(error.span, Span::new(6, 9));
+
+        // Bras and kets can also be labelled by the name of a state
+        assert!(matches!(dirac("|ψ⟩").unwrap().kind, Kind::NamedKet(_)));
+        match dirac("⟨ψ|0⟩").unwrap().kind {
+            Kind::Inner(bra, ket) => {
+                assert!(matches!(bra.kind, Kind::NamedBra(_)));
+                assert!(matches!(ket.kind, Kind::Ket(_)));
+                assert_eq!(ket.span, Span::new(5, 10));
+            }
+            kind => panic!("expected an inner product, got {:?}", kind),
+        }
+        assert!(matches!(
+            dirac("ψ = (|0⟩ + |1⟩)/√2; n = 1; ⟨ψ|Z_n").unwrap().kind,
+            Kind::LetState(..)
+        ));
+        assert!(matches!(dirac("|ψ⟩⟨0|").unwrap().kind, Kind::Mul(..)));
+    }
+
+    #[test]
+    fn errors() {
+        let error = dirac("|0> + |1>)").unwrap_err();
+        assert_eq!(error.message, "unexpected `)`");
+        assert_eq!(error.span, Span::new(9, 10));
+
+        let error = dirac("(|0> + |1>").unwrap_err();
+        assert_eq!(error.message, "unexpected end of input");
+        assert_eq!(error.span, Span::new(10, 10));
+
+        let error = dirac("|0> x 1.2.3").unwrap_err();
+        assert_eq!(error.message, "invalid number `1.2.3`");
+        assert_eq!(error.span, Span::new(6, 11));
+
+        let error = dirac("2 sqrt(1, 2)").unwrap_err();
+        assert_eq!(error.span, Span::new(2, 12));
+    }
 }
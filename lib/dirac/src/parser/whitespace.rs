@@ -1,16 +1,13 @@
+use super::{IResult, Input};
 use nom::character::complete::multispace0;
-use nom::error::ParseError;
 use nom::sequence::delimited;
-use nom::IResult;
 
 /// A combinator that takes a parser `inner` and produces a parser that also
 /// consumes both leading and trailing whitespace, returning the output of
 /// `inner`.
-pub fn ws<'a, F, O, E: ParseError<&'a str>>(
-    inner: F,
-) -> impl FnMut(&'a str) -> IResult<&'a str, O, E>
+pub fn ws<'a, F, O>(inner: F) -> impl FnMut(Input<'a>) -> IResult<'a, O>
 where
-    F: FnMut(&'a str) -> IResult<&'a str, O, E>,
+    F: FnMut(Input<'a>) -> IResult<'a, O>,
 {
     delimited(multispace0, inner, multispace0)
 }
@@ -0,0 +1,774 @@
+//! OpenQASM 2 and 3 front end. Circuits are lowered into the same expression
+//! tree as Dirac notation: every gate becomes a Kronecker product of its
+//! matrix with identities on the other qubits, with q[0] as the leftmost
+//! factor as for Pauli strings, and the circuit is the product of its gates.
+//!
+//! Register declarations, the standard gates with parameters such as
+//! `rz(pi/2)`, broadcasting over whole registers, `barrier` and comments are
+//! supported. Classical registers and includes are accepted and ignored.
+
+use crate::builtins::{Constant, Function};
+use crate::error::Error;
+use crate::expression::{Expression, Kind, Span};
+use crate::parser::{complete, identifier, span, IResult, Input, SyntaxError};
+use nom::branch::alt;
+use nom::bytes::complete::{is_not, tag, take_until};
+use nom::character::complete::{char, digit1, multispace1, satisfy};
+use nom::combinator::{eof, map, not, opt, peek, recognize, value};
+use nom::error::{ErrorKind, ParseError};
+use nom::multi::{many0, many_till, separated_list0, separated_list1};
+use nom::number::complete::recognize_float;
+use nom::sequence::{delimited, pair, preceded, terminated, tuple, Tuple};
+use num::complex::Complex64;
+
+/// A circuit read from OpenQASM, as the operators of its gates on the whole
+/// register, in the order they are applied
+#[derive(Debug, Clone)]
+pub struct Circuit {
+    pub width: usize,
+    pub gates: Vec<Expression>,
+}
+
+impl Circuit {
+    // The unitary G_k ... G_2 G_1 of the whole circuit
+    pub fn unitary(self) -> Expression {
+        let width = self.width;
+
+        self.gates
+            .into_iter()
+            .reduce(|product, gate| {
+                let span = gate.span.join(product.span);
+
+                Expression::new(Kind::Mul(Box::new(gate), Box::new(product)), span)
+            })
+            .unwrap_or_else(|| Term::new(Vec::new()).operator(width, Span::default()))
+    }
+
+    // The state the circuit prepares from |0...0>, applying the gates one at a
+    // time so that no product of gates is ever formed
+    pub fn state(self) -> Expression {
+        let ket = Expression::new(Kind::Ket("0".repeat(self.width)), Span::default());
+
+        self.gates.into_iter().fold(ket, |state, gate| {
+            let span = gate.span.join(state.span);
+
+            Expression::new(Kind::Mul(Box::new(gate), Box::new(state)), span)
+        })
+    }
+}
+
+// Parses an OpenQASM program into the circuit it describes
+pub fn qasm(input: &str) -> Result<Circuit, Error> {
+    let statements = complete(program(Input::new(input)))?;
+
+    // Registers can be used anywhere in the program, so they are all laid out
+    // before any gate is lowered
+    let mut registers: Vec<Register> = Vec::new();
+    for statement in statements.iter() {
+        if let Statement::Qubits(name, size) = statement {
+            if registers
+                .iter()
+                .any(|register| register.name == *name.fragment())
+            {
+                return Err(Error::new(
+                    format!("register `{}` is already declared", name.fragment()),
+                    fragment_span(*name),
+                ));
+            }
+
+            registers.push(Register {
+                name: name.to_string(),
+                offset: registers.iter().map(|register| register.size).sum(),
+                size: *size,
+            });
+        }
+    }
+
+    let width = registers.iter().map(|register| register.size).sum();
+    if width == 0 {
+        return Err(Error::new("no qubits are declared", Span::default()));
+    }
+
+    let mut gates = Vec::new();
+    for statement in statements {
+        if let Statement::Gate(gate) = statement {
+            for qubits in gate.applications(&registers)? {
+                gates.push(gate.lower(&qubits, width)?);
+            }
+        }
+    }
+
+    Ok(Circuit { width, gates })
+}
+
+struct Register {
+    name: String,
+    offset: usize,
+    size: usize,
+}
+
+#[derive(Clone)]
+enum Statement<'a> {
+    // Quantum register and its size
+    Qubits(Input<'a>, usize),
+    Gate(Application<'a>),
+    // Statements without effect on the state, such as classical registers
+    Ignored,
+}
+
+// A gate applied to qubits, or to whole registers when an operand has no index
+#[derive(Clone)]
+struct Application<'a> {
+    name: Input<'a>,
+    parameters: Vec<Expression>,
+    operands: Vec<(Input<'a>, Option<usize>, Span)>,
+    span: Span,
+}
+
+impl<'a> Application<'a> {
+    // Qubits each application of the gate acts on, applying it to the
+    // corresponding qubits of registers given as operands
+    fn applications(&self, registers: &[Register]) -> Result<Vec<Vec<usize>>, Error> {
+        let operands = self
+            .operands
+            .iter()
+            .map(|(name, index, span)| {
+                let register = registers
+                    .iter()
+                    .find(|register| register.name == *name.fragment())
+                    .ok_or_else(|| {
+                        Error::new(format!("unknown register `{}`", name.fragment()), *span)
+                    })?;
+
+                match index {
+                    Some(index) if *index < register.size => Ok(vec![register.offset + index]),
+                    Some(index) => Err(Error::new(
+                        format!(
+                            "qubit index {} is out of range for register `{}` of size {}",
+                            index, register.name, register.size
+                        ),
+                        *span,
+                    )),
+                    None => Ok((register.offset..register.offset + register.size).collect()),
+                }
+            })
+            .collect::<Result<Vec<Vec<usize>>, Error>>()?;
+
+        let count = operands.iter().map(Vec::len).max().unwrap_or(1);
+        if operands
+            .iter()
+            .any(|qubits| qubits.len() != 1 && qubits.len() != count)
+        {
+            return Err(Error::new(
+                "registers of different sizes are applied together",
+                self.span,
+            ));
+        }
+
+        Ok((0..count)
+            .map(|k| {
+                operands
+                    .iter()
+                    .map(|qubits| qubits[k.min(qubits.len() - 1)])
+                    .collect()
+            })
+            .collect())
+    }
+
+    // Operator of the gate on the given qubits of a register of the given
+    // width
+    fn lower(&self, qubits: &[usize], width: usize) -> Result<Expression, Error> {
+        let name = *self.name.fragment();
+        let (parameters, arity) = match gate_arity(name) {
+            Some(arity) => arity,
+            None => {
+                return Err(Error::new(
+                    format!("unknown gate `{}`", name),
+                    fragment_span(self.name),
+                ))
+            }
+        };
+
+        if self.parameters.len() != parameters {
+            return Err(Error::new(
+                format!(
+                    "`{}` takes {} parameter(s) but {} were given",
+                    name,
+                    parameters,
+                    self.parameters.len()
+                ),
+                self.span,
+            ));
+        }
+        if qubits.len() != arity {
+            return Err(Error::new(
+                format!(
+                    "`{}` acts on {} qubit(s) but {} were given",
+                    name,
+                    arity,
+                    qubits.len()
+                ),
+                self.span,
+            ));
+        }
+        if let Some(qubit) = qubits
+            .iter()
+            .enumerate()
+            .find_map(|(k, qubit)| qubits[..k].contains(qubit).then_some(qubit))
+        {
+            return Err(Error::new(
+                format!("`{}` is applied to qubit {} twice", name, qubit),
+                self.span,
+            ));
+        }
+
+        let gates = Gates { span: self.span };
+        let p = |k: usize| self.parameters[k].clone();
+
+        let operator = match (name, qubits) {
+            ("swap", [a, b]) => gates.swap(*a, *b, width),
+            ("cswap" | "fredkin", [c, a, b]) => gates.product(vec![
+                gates.controlled(&[*b], *a, gates.constant(Constant::X), width),
+                gates.controlled(&[*c, *a], *b, gates.constant(Constant::X), width),
+                gates.controlled(&[*b], *a, gates.constant(Constant::X), width),
+            ]),
+            (_, [target]) => {
+                let matrix = gates.single(name, p).unwrap();
+
+                Term::new(vec![(*target, matrix)]).operator(width, self.span)
+            }
+            (_, [controls @ .., target]) => {
+                let base = match name {
+                    "ccx" | "toffoli" => "x",
+                    _ => &name[1..],
+                };
+                let matrix = gates.single(base, p).unwrap();
+
+                gates.controlled(controls, *target, matrix, width)
+            }
+            _ => unreachable!("every gate acts on at least one qubit"),
+        };
+
+        Ok(operator)
+    }
+}
+
+// Number of parameters and qubits of the standard gates
+fn gate_arity(name: &str) -> Option<(usize, usize)> {
+    match name {
+        "id" | "x" | "y" | "z" | "h" | "s" | "sdg" | "t" | "tdg" | "sx" | "sxdg" => Some((0, 1)),
+        "rx" | "ry" | "rz" | "p" | "phase" | "u1" => Some((1, 1)),
+        "u2" => Some((2, 1)),
+        "u" | "U" | "u3" => Some((3, 1)),
+        "cx" | "CX" | "cnot" | "cy" | "cz" | "ch" | "swap" => Some((0, 2)),
+        "crx" | "cry" | "crz" | "cp" | "cphase" | "cu1" => Some((1, 2)),
+        "cu3" => Some((3, 2)),
+        "ccx" | "toffoli" | "cswap" | "fredkin" => Some((0, 3)),
+        _ => None,
+    }
+}
+
+// Kronecker product of single qubit matrices on some qubits, with identities
+// on the other qubits
+struct Term {
+    factors: Vec<(usize, Expression)>,
+}
+
+impl Term {
+    fn new(factors: Vec<(usize, Expression)>) -> Term {
+        Term { factors }
+    }
+
+    fn operator(mut self, width: usize, span: Span) -> Expression {
+        let factor = |qubit: usize, factors: &mut Vec<(usize, Expression)>| match factors
+            .iter()
+            .position(|(site, _)| *site == qubit)
+        {
+            Some(k) => factors.remove(k).1,
+            None => Expression::new(Kind::Constant(Constant::I), span),
+        };
+
+        let first = factor(0, &mut self.factors);
+        (1..width).fold(first, |product, qubit| {
+            let factor = factor(qubit, &mut self.factors);
+
+            Expression::new(Kind::Kronecker(Box::new(product), Box::new(factor)), span)
+        })
+    }
+}
+
+// Builds the matrices of gates, with nodes spanning the gate's statement
+struct Gates {
+    span: Span,
+}
+
+impl Gates {
+    fn node(&self, kind: Kind) -> Expression {
+        Expression::new(kind, self.span)
+    }
+
+    fn constant(&self, constant: Constant) -> Expression {
+        self.node(Kind::Constant(constant))
+    }
+
+    fn scalar(&self, re: f64, im: f64) -> Expression {
+        self.node(Kind::Scalar(Complex64::new(re, im)))
+    }
+
+    fn mul(&self, a: Expression, b: Expression) -> Expression {
+        self.node(Kind::Mul(Box::new(a), Box::new(b)))
+    }
+
+    fn add(&self, a: Expression, b: Expression) -> Expression {
+        self.node(Kind::Add(Box::new(a), Box::new(b)))
+    }
+
+    fn sub(&self, a: Expression, b: Expression) -> Expression {
+        self.node(Kind::Sub(Box::new(a), Box::new(b)))
+    }
+
+    // |a><b|
+    fn outer(&self, a: u8, b: u8) -> Expression {
+        self.node(Kind::Outer(a.to_string(), b.to_string()))
+    }
+
+    fn function(&self, function: Function, arg: Expression) -> Expression {
+        self.node(Kind::Function(function, vec![arg]))
+    }
+
+    // e^{i c θ} for a rational c
+    fn phase(&self, c: f64, theta: Expression) -> Expression {
+        self.function(Function::Exp, self.mul(self.scalar(0.0, c), theta))
+    }
+
+    // θ/2, as cos and sin of rotations take it
+    fn half(&self, theta: Expression) -> Expression {
+        self.node(Kind::Div(Box::new(theta), Box::new(self.scalar(2.0, 0.0))))
+    }
+
+    // cos(θ/2) I - i sin(θ/2) P
+    fn rotation(&self, pauli: Constant, theta: Expression) -> Expression {
+        let cos = self.function(Function::Cos, self.half(theta.clone()));
+        let sin = self.function(Function::Sin, self.half(theta));
+
+        self.sub(
+            self.mul(cos, self.constant(Constant::I)),
+            self.mul(self.mul(self.scalar(0.0, 1.0), sin), self.constant(pauli)),
+        )
+    }
+
+    // |0><0| + c |1><1|
+    fn diagonal(&self, c: Expression) -> Expression {
+        self.add(self.outer(0, 0), self.mul(c, self.outer(1, 1)))
+    }
+
+    // The general single qubit gate
+    // [[cos(θ/2), -e^{iλ} sin(θ/2)], [e^{iφ} sin(θ/2), e^{i(φ+λ)} cos(θ/2)]]
+    fn u(&self, theta: Expression, phi: Expression, lambda: Expression) -> Expression {
+        let cos = || self.function(Function::Cos, self.half(theta.clone()));
+        let sin = || self.function(Function::Sin, self.half(theta.clone()));
+        let both = self.add(phi.clone(), lambda.clone());
+
+        let entries = [
+            (self.scalar(1.0, 0.0), cos(), 0, 0),
+            (
+                self.mul(self.scalar(-1.0, 0.0), self.phase(1.0, lambda)),
+                sin(),
+                0,
+                1,
+            ),
+            (self.phase(1.0, phi), sin(), 1, 0),
+            (self.phase(1.0, both), cos(), 1, 1),
+        ];
+
+        entries
+            .into_iter()
+            .map(|(phase, amplitude, a, b)| self.mul(self.mul(phase, amplitude), self.outer(a, b)))
+            .reduce(|sum, entry| self.add(sum, entry))
+            .unwrap()
+    }
+
+    // Matrix of a single qubit gate, given a way to get its parameters
+    fn single(&self, name: &str, p: impl Fn(usize) -> Expression) -> Option<Expression> {
+        let pi = || self.constant(Constant::Pi);
+        let quarter = || self.node(Kind::Div(Box::new(pi()), Box::new(self.scalar(4.0, 0.0))));
+
+        let matrix = match name {
+            "id" => self.constant(Constant::I),
+            "x" | "X" | "not" => self.constant(Constant::X),
+            "y" => self.constant(Constant::Y),
+            "z" => self.constant(Constant::Z),
+            "h" => self.constant(Constant::H),
+            "s" => self.diagonal(self.scalar(0.0, 1.0)),
+            "sdg" => self.diagonal(self.scalar(0.0, -1.0)),
+            "t" => self.diagonal(self.phase(1.0, quarter())),
+            "tdg" => self.diagonal(self.phase(-1.0, quarter())),
+            // (1 ± i)/2 I + (1 ∓ i)/2 X
+            "sx" | "sxdg" => {
+                let sign = if name == "sx" { 1.0 } else { -1.0 };
+
+                self.add(
+                    self.mul(self.scalar(0.5, 0.5 * sign), self.constant(Constant::I)),
+                    self.mul(self.scalar(0.5, -0.5 * sign), self.constant(Constant::X)),
+                )
+            }
+            "rx" => self.rotation(Constant::X, p(0)),
+            "ry" => self.rotation(Constant::Y, p(0)),
+            // e^{-iθ/2} |0><0| + e^{iθ/2} |1><1|
+            "rz" => self.add(
+                self.mul(self.phase(-0.5, p(0)), self.outer(0, 0)),
+                self.mul(self.phase(0.5, p(0)), self.outer(1, 1)),
+            ),
+            "p" | "phase" | "u1" => self.diagonal(self.phase(1.0, p(0))),
+            "u2" => self.u(self.half(pi()), p(0), p(1)),
+            "u" | "U" | "u3" => self.u(p(0), p(1), p(2)),
+            _ => return None,
+        };
+
+        Some(matrix)
+    }
+
+    // The gate applying a matrix to the target when all controls are |1>:
+    // |0><0| x I + |1><1| x U for one control, and in general
+    // I - P + P x U with P the projector on the controls all being |1>
+    fn controlled(
+        &self,
+        controls: &[usize],
+        target: usize,
+        matrix: Expression,
+        width: usize,
+    ) -> Expression {
+        let projector = |k: u8| -> Vec<(usize, Expression)> {
+            controls
+                .iter()
+                .map(|control| (*control, self.outer(k, k)))
+                .collect()
+        };
+
+        let mut applied = projector(1);
+        applied.push((target, matrix));
+        let applied = Term::new(applied).operator(width, self.span);
+
+        match controls {
+            [_] => self.add(Term::new(projector(0)).operator(width, self.span), applied),
+            _ => self.add(
+                self.sub(
+                    Term::new(Vec::new()).operator(width, self.span),
+                    Term::new(projector(1)).operator(width, self.span),
+                ),
+                applied,
+            ),
+        }
+    }
+
+    // Three CNOTs, alternating the control
+    fn swap(&self, a: usize, b: usize, width: usize) -> Expression {
+        let cx = |control: usize, target: usize| {
+            self.controlled(&[control], target, self.constant(Constant::X), width)
+        };
+
+        self.product(vec![cx(a, b), cx(b, a), cx(a, b)])
+    }
+
+    // Product of operators applied in order, the first one rightmost
+    fn product(&self, operators: Vec<Expression>) -> Expression {
+        operators
+            .into_iter()
+            .reduce(|product, operator| self.mul(operator, product))
+            .unwrap()
+    }
+}
+
+fn fragment_span(input: Input) -> Span {
+    Span::new(
+        input.location_offset(),
+        input.location_offset() + input.fragment().len(),
+    )
+}
+
+// Whitespace and comments
+fn blank(input: Input) -> IResult<()> {
+    let line = preceded(tag("//"), opt(is_not("\n")));
+    let block = delimited(tag("/*"), take_until("*/"), tag("*/"));
+
+    value(
+        (),
+        many0(alt((
+            value((), multispace1),
+            value((), line),
+            value((), block),
+        ))),
+    )(input)
+}
+
+// A parser that also consumes the whitespace and comments around its input
+fn token<'a, F, O>(inner: F) -> impl FnMut(Input<'a>) -> IResult<'a, O>
+where
+    F: FnMut(Input<'a>) -> IResult<'a, O>,
+{
+    delimited(blank, inner, blank)
+}
+
+// A keyword, not followed by more of an identifier
+fn keyword<'a>(word: &'static str) -> impl FnMut(Input<'a>) -> IResult<'a, Input<'a>> {
+    token(terminated(
+        tag(word),
+        not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_'))),
+    ))
+}
+
+fn size(input: Input) -> IResult<usize> {
+    let (rem, digits) = token(digit1)(input)?;
+
+    match digits.fragment().parse() {
+        Ok(n) => Ok((rem, n)),
+        Err(_) => Err(nom::Err::Failure(SyntaxError::new(
+            input,
+            Error::new(
+                format!("integer `{}` is too large", digits.fragment()),
+                span(input, rem),
+            ),
+        ))),
+    }
+}
+
+fn subscript(input: Input) -> IResult<usize> {
+    delimited(token(char('[')), size, token(char(']')))(input)
+}
+
+// OPENQASM 2.0; and include "qelib1.inc";
+fn preamble(input: Input) -> IResult<Statement> {
+    let version = recognize(pair(digit1, opt(pair(char('.'), digit1))));
+    let file = delimited(char('"'), take_until("\""), char('"'));
+
+    value(
+        Statement::Ignored,
+        alt((
+            terminated(preceded(keyword("OPENQASM"), token(version)), char(';')),
+            terminated(preceded(keyword("include"), token(file)), char(';')),
+        )),
+    )(input)
+}
+
+// qreg q[2]; and qubit[2] q; or qubit q;
+fn qubits(input: Input) -> IResult<Statement> {
+    let qreg = map(
+        tuple((keyword("qreg"), token(identifier), subscript)),
+        |(_, name, size)| Statement::Qubits(name, size),
+    );
+    let qubit = map(
+        tuple((keyword("qubit"), opt(subscript), token(identifier))),
+        |(_, size, name)| Statement::Qubits(name, size.unwrap_or(1)),
+    );
+
+    terminated(alt((qreg, qubit)), char(';'))(input)
+}
+
+// creg c[2]; bit[2] c; and barrier q;
+fn classical(input: Input) -> IResult<Statement> {
+    let creg = tuple((keyword("creg"), token(identifier), subscript));
+    let bit = tuple((keyword("bit"), opt(subscript), token(identifier)));
+    let barrier = pair(keyword("barrier"), separated_list0(char(','), operand));
+
+    value(
+        Statement::Ignored,
+        terminated(
+            alt((value((), creg), value((), bit), value((), barrier))),
+            char(';'),
+        ),
+    )(input)
+}
+
+// Statements that cannot be part of a unitary circuit, or define new gates
+fn unsupported(input: Input) -> IResult<Statement> {
+    let (rem, word) = alt((
+        keyword("measure"),
+        keyword("reset"),
+        keyword("if"),
+        keyword("gate"),
+        keyword("opaque"),
+        keyword("def"),
+    ))(input)?;
+
+    Err(nom::Err::Failure(SyntaxError::new(
+        input,
+        Error::new(
+            format!(
+                "`{}` is not supported, only unitary circuits of standard gates are",
+                word.fragment()
+            ),
+            span(input, rem),
+        ),
+    )))
+}
+
+// A qubit q[0] or a whole register q
+fn operand(input: Input) -> IResult<(Input, Option<usize>, Span)> {
+    let (rem, (name, index)) = (token(identifier), opt(subscript)).parse(input)?;
+
+    Ok((rem, (name, index, span(input, rem))))
+}
+
+// h q[0]; and rz(pi/2) q[1]; and cx q[0], q[1];
+fn gate(input: Input) -> IResult<Statement> {
+    let parameters = delimited(
+        token(char('(')),
+        separated_list1(char(','), parameter),
+        token(char(')')),
+    );
+    let (rem, (name, parameters, operands)) = (
+        token(identifier),
+        opt(parameters),
+        separated_list1(char(','), operand),
+    )
+        .parse(input)?;
+    let span = span(input, rem);
+    let (rem, _) = char(';')(rem).map_err(|_: nom::Err<SyntaxError>| {
+        nom::Err::Failure(SyntaxError::new(
+            rem,
+            Error::new("expected `;` after the gate", Span::new(span.end, span.end)),
+        ))
+    })?;
+
+    Ok((
+        rem,
+        Statement::Gate(Application {
+            name,
+            parameters: parameters.unwrap_or_default(),
+            operands,
+            span,
+        }),
+    ))
+}
+
+// Statements up to the end of the input, so that a statement that does not
+// parse is reported rather than ending the program early
+fn program(input: Input) -> IResult<Vec<Statement>> {
+    let statement = alt((preamble, qubits, classical, unsupported, gate));
+    let (rem, (statements, _)) = preceded(blank, many_till(token(statement), eof))(input)?;
+
+    Ok((rem, statements))
+}
+
+// A real literal such as 2, 0.5 or 1e-3
+fn real(input: Input) -> IResult<Kind> {
+    let (rem, digits) = recognize_float(input)?;
+
+    match digits.fragment().parse::<f64>() {
+        Ok(x) => Ok((rem, Kind::Scalar(Complex64::new(x, 0.0)))),
+        Err(_) => Err(nom::Err::Error(SyntaxError::from_error_kind(
+            input,
+            ErrorKind::Float,
+        ))),
+    }
+}
+
+// pi, tau and euler, also written π and τ
+fn named(input: Input) -> IResult<Kind> {
+    let (rem, name) = alt((identifier, recognize(char('π')), recognize(char('τ'))))(input)?;
+    let node = |kind| Box::new(Expression::new(kind, span(input, rem)));
+
+    match *name.fragment() {
+        "pi" | "π" => Ok((rem, Kind::Constant(Constant::Pi))),
+        "euler" => Ok((rem, Kind::Constant(Constant::E))),
+        // τ = 2π
+        "tau" | "τ" => Ok((
+            rem,
+            Kind::Mul(
+                node(Kind::Scalar(Complex64::new(2.0, 0.0))),
+                node(Kind::Constant(Constant::Pi)),
+            ),
+        )),
+        _ => Err(nom::Err::Error(SyntaxError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        ))),
+    }
+}
+
+// Scalar functions of a parameter, such as cos(θ)
+fn call(input: Input) -> IResult<Kind> {
+    let (rem, (name, arg)) = (
+        identifier,
+        delimited(token(char('(')), parameter, char(')')),
+    )
+        .parse(input)?;
+
+    match Function::from_name(name.fragment()) {
+        Some(
+            function @ (Function::Sin
+            | Function::Cos
+            | Function::Exp
+            | Function::Ln
+            | Function::Sqrt),
+        ) => Ok((rem, Kind::Function(function, vec![arg]))),
+        _ => Err(nom::Err::Failure(SyntaxError::new(
+            input,
+            Error::new(
+                format!("unknown function `{}`", name.fragment()),
+                span(input, rem),
+            ),
+        ))),
+    }
+}
+
+// Numbers, constants, function calls and parenthised parameters
+fn primary(input: Input) -> IResult<Expression> {
+    let parenthised = map(delimited(char('('), parameter, char(')')), |expr| {
+        Kind::Parenthised(Box::new(expr))
+    });
+
+    let (rem, kind) = token(alt((real, call, named, parenthised)))(input)?;
+
+    Ok((rem, Expression::new(kind, span(input, rem))))
+}
+
+// Negated parameters -θ
+fn unary(input: Input) -> IResult<Expression> {
+    let (rem, (minus, expr)) = (opt(token(char('-'))), primary).parse(input)?;
+
+    match minus {
+        Some(_) => Ok((
+            rem,
+            Expression::new(Kind::AdditiveInverse(Box::new(expr)), span(input, rem)),
+        )),
+        None => Ok((rem, expr)),
+    }
+}
+
+fn binary<'a>(
+    operand: fn(Input<'a>) -> IResult<'a, Expression>,
+    operators: &'static str,
+) -> impl FnMut(Input<'a>) -> IResult<'a, Expression> {
+    move |input| {
+        let operator = satisfy(|c| operators.contains(c));
+        let (rem, (first, rest)) = (operand, many0(pair(operator, operand))).parse(input)?;
+
+        Ok((
+            rem,
+            rest.into_iter().fold(first, |a, (op, b)| {
+                let span = a.span.join(b.span);
+                let (a, b) = (Box::new(a), Box::new(b));
+
+                let kind = match op {
+                    '+' => Kind::Add(a, b),
+                    '-' => Kind::Sub(a, b),
+                    '*' => Kind::Mul(a, b),
+                    '/' => Kind::Div(a, b),
+                    _ => unreachable!("should only ever match +, -, *, /"),
+                };
+
+                Expression::new(kind, span)
+            }),
+        ))
+    }
+}
+
+fn factor(input: Input) -> IResult<Expression> {
+    binary(unary, "*/")(input)
+}
+
+// A gate parameter: real arithmetic over numbers and pi
+fn parameter(input: Input) -> IResult<Expression> {
+    binary(factor, "+-")(input)
+}
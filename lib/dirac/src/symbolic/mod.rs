@@ -0,0 +1,534 @@
+//! Exact evaluation of expressions whose amplitudes are built from rationals,
+//! square roots and roots of unity, such as `(|0> + |1>)/√2`.
+
+mod number;
+
+pub use number::Exact;
+
+use number::{MAX_BITS, MAX_EXPONENT};
+
+use crate::builtins::{Constant, Function};
+use crate::expression::{pauli_string, restore, state, terms, Context, Expression, Kind};
+use crate::tensor::Tensor;
+use num::{BigRational, ToPrimitive, Zero};
+use std::fmt::{self, Display};
+
+// Largest number of entries of an exact tensor. Larger tensors are left to
+// the floating point evaluation, which is much cheaper per entry.
+const MAX_ENTRIES: usize = 1 << 12;
+
+/// A tensor of exact complex numbers, stored in row-major order like `Tensor`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExactTensor {
+    pub data: Vec<Exact>,
+    pub shape: (usize, usize),
+}
+
+impl ExactTensor {
+    fn new(data: Vec<Exact>, shape: (usize, usize)) -> Option<ExactTensor> {
+        match data.len() <= MAX_ENTRIES {
+            true => Some(ExactTensor { data, shape }),
+            false => None,
+        }
+    }
+
+    pub fn scalar(x: Exact) -> ExactTensor {
+        ExactTensor {
+            data: vec![x],
+            shape: (1, 1),
+        }
+    }
+
+    pub fn item(&self) -> Option<&Exact> {
+        match self.shape {
+            (1, 1) => self.data.first(),
+            _ => None,
+        }
+    }
+
+    // Converts a tensor whose entries all have exact values, such as the
+    // Pauli matrices
+    pub fn from_tensor(tensor: &Tensor) -> Option<ExactTensor> {
+        let data = tensor
+            .data
+            .iter()
+            .map(|c| Exact::from_complex(*c))
+            .collect::<Option<Vec<Exact>>>()?;
+
+        ExactTensor::new(data, tensor.shape)
+    }
+
+    // Basis state from a sequence of 01+- characters
+    fn basis(label: &str) -> Option<ExactTensor> {
+        let half = Exact::integer(2).sqrt()?.inv()?;
+
+        label
+            .chars()
+            .map(|c| {
+                let (a, b) = match c {
+                    '0' => (Exact::integer(1), Exact::zero()),
+                    '1' => (Exact::zero(), Exact::integer(1)),
+                    '+' => (half.clone(), half.clone()),
+                    '-' => (half.clone(), &-&Exact::i() * &half),
+                    _ => return None,
+                };
+
+                ExactTensor::new(vec![a, b], (2, 1))
+            })
+            .try_fold(ExactTensor::scalar(Exact::integer(1)), |product, qubit| {
+                product.prod(&qubit?)
+            })
+    }
+
+    fn map<F: Fn(&Exact) -> Exact>(&self, f: F) -> ExactTensor {
+        ExactTensor {
+            data: self.data.iter().map(f).collect(),
+            shape: self.shape,
+        }
+    }
+
+    fn scale(&self, x: &Exact) -> ExactTensor {
+        self.map(|y| x * y)
+    }
+
+    // Conjugate transpose
+    fn dag(&self) -> ExactTensor {
+        let (m, n) = self.shape;
+
+        ExactTensor {
+            data: (0..m * n)
+                .map(|k| self.data[(k % m) * n + k / m].conj())
+                .collect(),
+            shape: (n, m),
+        }
+    }
+
+    fn add(&self, rhs: &ExactTensor) -> Option<ExactTensor> {
+        match self.shape == rhs.shape {
+            true => ExactTensor::new(
+                self.data
+                    .iter()
+                    .zip(rhs.data.iter())
+                    .map(|(a, b)| a + b)
+                    .collect(),
+                self.shape,
+            ),
+            false => None,
+        }
+    }
+
+    fn sub(&self, rhs: &ExactTensor) -> Option<ExactTensor> {
+        self.add(&rhs.scale(&Exact::integer(-1)))
+    }
+
+    // Matrix product, where 1x1 tensors act as scalars
+    fn mul(&self, rhs: &ExactTensor) -> Option<ExactTensor> {
+        if let Some(x) = self.item() {
+            return Some(rhs.scale(x));
+        }
+        if let Some(x) = rhs.item() {
+            return Some(self.scale(x));
+        }
+
+        let ((m, k), (l, n)) = (self.shape, rhs.shape);
+        if k != l {
+            return None;
+        }
+
+        ExactTensor::new(
+            (0..m * n)
+                .map(|ij| {
+                    (0..k).fold(Exact::zero(), |sum, p| {
+                        &sum + &(&self.data[(ij / n) * k + p] * &rhs.data[p * n + ij % n])
+                    })
+                })
+                .collect(),
+            (m, n),
+        )
+    }
+
+    // Kronecker product
+    fn prod(&self, rhs: &ExactTensor) -> Option<ExactTensor> {
+        let ((m, n), (p, q)) = (self.shape, rhs.shape);
+
+        if m * n * p * q > MAX_ENTRIES {
+            return None;
+        }
+
+        ExactTensor::new(
+            (0..m * p * n * q)
+                .map(|k| {
+                    let (i, j) = (k / (n * q), k % (n * q));
+
+                    &self.data[(i / p) * n + j / q] * &rhs.data[(i % p) * q + j % q]
+                })
+                .collect(),
+            (m * p, n * q),
+        )
+    }
+
+    fn norm_sqr(&self) -> Exact {
+        self.data
+            .iter()
+            .fold(Exact::zero(), |sum, x| &sum + &x.norm_sqr())
+    }
+}
+
+impl From<&ExactTensor> for Tensor {
+    fn from(tensor: &ExactTensor) -> Tensor {
+        Tensor::new(
+            tensor.data.iter().map(Exact::to_complex).collect(),
+            tensor.shape,
+        )
+    }
+}
+
+impl Expression {
+    // Evaluates the expression exactly, or returns None when it involves
+    // numbers outside of what Exact represents, such as ln(2), or operations
+    // without an exact implementation, such as matrix functions
+    pub fn exact(&self) -> Option<ExactTensor> {
+        self.exact_in(&mut Context::new(self))
+    }
+
+    fn exact_in(&self, context: &mut Context<ExactTensor>) -> Option<ExactTensor> {
+        match &self.kind {
+            Kind::Scalar(c) => Some(ExactTensor::scalar(Exact::from_complex(*c)?)),
+            // (X + Z)/√2
+            Kind::Constant(Constant::H) => {
+                let (x, z) = (
+                    ExactTensor::from_tensor(&Constant::X.value())?,
+                    ExactTensor::from_tensor(&Constant::Z.value())?,
+                );
+
+                Some(x.add(&z)?.scale(&Exact::integer(2).sqrt()?.inv()?))
+            }
+            Kind::Constant(constant) => ExactTensor::from_tensor(&constant.value()),
+            Kind::Bra(bra) => Some(ExactTensor::basis(bra)?.dag()),
+            Kind::Ket(ket) => ExactTensor::basis(ket),
+            Kind::NamedBra(name) => Some(state(&context.states, name).ok()?.dag()),
+            Kind::NamedKet(name) => state(&context.states, name).ok().cloned(),
+            Kind::Outer(ket, bra) => ExactTensor::basis(ket)?.mul(&ExactTensor::basis(bra)?.dag()),
+            Kind::AdditiveInverse(expr) => Some(expr.exact_in(context)?.scale(&Exact::integer(-1))),
+            Kind::Dagger(expr) => Some(expr.exact_in(context)?.dag()),
+            Kind::Mul(a, b) => a.exact_in(context)?.mul(&b.exact_in(context)?),
+            Kind::Div(a, b) => {
+                let (a, b) = (a.exact_in(context)?, b.exact_in(context)?);
+
+                Some(a.scale(&b.item()?.inv()?))
+            }
+            Kind::Add(a, b) => a.exact_in(context)?.add(&b.exact_in(context)?),
+            Kind::Sub(a, b) => a.exact_in(context)?.sub(&b.exact_in(context)?),
+            Kind::Kronecker(a, b) => a.exact_in(context)?.prod(&b.exact_in(context)?),
+            Kind::Pow(base, exponent) => {
+                pow(&base.exact_in(context)?, &exponent.exact_in(context)?)
+            }
+            Kind::Inner(a, b) => {
+                let (a, b) = (a.exact_in(context)?, b.exact_in(context)?);
+
+                match a.data.len() == b.data.len() {
+                    true => Some(ExactTensor::scalar(
+                        a.data
+                            .iter()
+                            .zip(b.data.iter())
+                            .fold(Exact::zero(), |sum, (x, y)| &sum + &(x * y)),
+                    )),
+                    false => None,
+                }
+            }
+            Kind::Commutator(a, b) | Kind::Anticommutator(a, b) => {
+                let (a, b) = (a.exact_in(context)?, b.exact_in(context)?);
+                let (ab, ba) = (a.mul(&b)?, b.mul(&a)?);
+
+                match &self.kind {
+                    Kind::Commutator(..) => ab.sub(&ba),
+                    _ => ab.add(&ba),
+                }
+            }
+            Kind::Parenthised(expr) => expr.exact_in(context),
+            Kind::Norm(expr) => Some(ExactTensor::scalar(
+                expr.exact_in(context)?.norm_sqr().sqrt()?,
+            )),
+            Kind::Function(function, args) => apply(*function, args, context),
+            // Pauli strings only have entries 0, ±1 and ±i
+            Kind::Pauli(factors) => ExactTensor::from_tensor(
+                &pauli_string(factors, &context.variables, context.width).ok()?,
+            ),
+            Kind::Sum(variable, from, to, body) => {
+                let (from, to) = (from.value(&context.variables), to.value(&context.variables));
+                let shadowed = context.variables.get(variable).cloned();
+                let mut sum: Option<ExactTensor> = None;
+
+                for value in terms(variable, from, to, self.span).ok()? {
+                    context.variables.insert(variable.clone(), value);
+
+                    let term = body.exact_in(context)?;
+                    sum = match sum {
+                        Some(sum) => Some(sum.add(&term)?),
+                        None => Some(term),
+                    };
+                }
+
+                restore(&mut context.variables, variable, shadowed);
+
+                sum
+            }
+            Kind::Let(variable, value, body) => {
+                let value = value.value(&context.variables);
+                let shadowed = context.variables.insert(variable.clone(), value);
+                let result = body.exact_in(context);
+
+                restore(&mut context.variables, variable, shadowed);
+
+                result
+            }
+            Kind::LetState(name, value, body) => {
+                let value = value.exact_in(context)?;
+                let shadowed = context.states.insert(name.clone(), value);
+                let result = body.exact_in(context);
+
+                restore(&mut context.states, name, shadowed);
+
+                result
+            }
+        }
+    }
+
+    // Writes a scalar expression as c π^k, so that the arguments of exp, sin
+    // and cos can be recognised as rational multiples of π
+    fn angle(&self, context: &mut Context<ExactTensor>) -> Option<(Exact, i32)> {
+        match &self.kind {
+            Kind::Constant(Constant::Pi) => Some((Exact::integer(1), 1)),
+            Kind::Parenthised(expr) => expr.angle(context),
+            Kind::AdditiveInverse(expr) => {
+                let (c, k) = expr.angle(context)?;
+
+                Some((-&c, k))
+            }
+            Kind::Mul(a, b) => {
+                let ((c, k), (d, l)) = (a.angle(context)?, b.angle(context)?);
+
+                Some((&c * &d, k + l))
+            }
+            Kind::Div(a, b) => {
+                let ((c, k), (d, l)) = (a.angle(context)?, b.angle(context)?);
+
+                Some((&c * &d.inv()?, k - l))
+            }
+            // Sums of terms with the same power of π, or with a zero term, as
+            // for the phases φ + λ of QASM gates
+            Kind::Add(a, b) | Kind::Sub(a, b) => {
+                let ((c, k), (d, l)) = (a.angle(context)?, b.angle(context)?);
+                let d = match self.kind {
+                    Kind::Sub(..) => -&d,
+                    _ => d,
+                };
+
+                match (k == l, c.is_zero(), d.is_zero()) {
+                    (true, _, _) => Some((&c + &d, k)),
+                    (_, true, _) => Some((d, l)),
+                    (_, _, true) => Some((c, k)),
+                    _ => None,
+                }
+            }
+            _ => Some((self.exact_in(context)?.item()?.clone(), 0)),
+        }
+    }
+}
+
+// Applies a function exactly. Exponentials, sines and cosines are exact at
+// rational multiples of π, where they are roots of unity.
+fn apply(
+    function: Function,
+    args: &[Expression],
+    context: &mut Context<ExactTensor>,
+) -> Option<ExactTensor> {
+    let scalar = |x: Exact| Some(ExactTensor::scalar(x));
+
+    match function {
+        Function::Exp | Function::Sin | Function::Cos => {
+            let (c, k) = args.first()?.angle(context)?;
+
+            let theta = match (k, function) {
+                (_, _) if c.is_zero() => BigRational::zero(),
+                // exp(iπθ)
+                (1, Function::Exp) => (&c * &-&Exact::i()).to_rational()?,
+                (1, _) => c.to_rational()?,
+                _ => return None,
+            };
+            let root = Exact::root_of_unity(theta);
+
+            match function {
+                Function::Exp => scalar(root),
+                Function::Sin => scalar(root.im()),
+                _ => scalar(root.re()),
+            }
+        }
+        _ => {
+            let arg = args
+                .iter()
+                .map(|arg| arg.exact_in(context))
+                .collect::<Option<Vec<_>>>()?;
+            let arg = arg.first()?;
+
+            match function {
+                Function::Sqrt => scalar(arg.item()?.sqrt()?),
+                Function::Abs => scalar(arg.item()?.norm_sqr().sqrt()?),
+                Function::Conj => Some(arg.map(Exact::conj)),
+                Function::Re => Some(arg.map(Exact::re)),
+                Function::Im => Some(arg.map(Exact::im)),
+                Function::Tr if arg.shape.0 == arg.shape.1 => {
+                    scalar((0..arg.shape.0).fold(Exact::zero(), |sum, i| {
+                        &sum + &arg.data[i * arg.shape.1 + i]
+                    }))
+                }
+                Function::Normalize => Some(arg.scale(&arg.norm_sqr().sqrt()?.inv()?)),
+                _ => None,
+            }
+        }
+    }
+}
+
+// Raises a scalar to a rational power with denominator 1 or 2, or a square
+// operator to a non-negative integer power
+fn pow(base: &ExactTensor, exponent: &ExactTensor) -> Option<ExactTensor> {
+    let exponent = exponent.item()?.to_rational()?;
+    let n = exponent.numer().to_i64()?;
+
+    match (base.item(), exponent.denom().to_i64()?) {
+        (Some(x), 1) => Some(ExactTensor::scalar(x.powi(n)?)),
+        (Some(x), 2) => Some(ExactTensor::scalar(x.sqrt()?.powi(n)?)),
+        (None, 1) if n >= 0 && base.shape.0 == base.shape.1 => matrix_power(base, n as u64),
+        _ => None,
+    }
+}
+
+// Power of a square operator by repeated squaring
+fn matrix_power(base: &ExactTensor, mut n: u64) -> Option<ExactTensor> {
+    if n > MAX_EXPONENT {
+        return None;
+    }
+
+    let bits = |x: &ExactTensor| x.data.iter().map(Exact::bits).max().unwrap_or(0);
+    let mut base = base.clone();
+    let mut power = ExactTensor::scalar(Exact::integer(1));
+
+    while n > 0 {
+        if n & 1 == 1 {
+            power = power.mul(&base)?;
+        }
+        n >>= 1;
+        if n > 0 {
+            base = base.mul(&base)?;
+        }
+        if bits(&power) > MAX_BITS || bits(&base) > MAX_BITS {
+            return None;
+        }
+    }
+
+    Some(power)
+}
+
+impl Display for ExactTensor {
+    // Vectors are written in Dirac notation with a common factor pulled out,
+    // (|0> + |1>)/√2, and other tensors entry by entry
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |k: usize| match self.data.len() {
+            n if n.is_power_of_two() && n > 1 => {
+                format!("{:0width$b}", k, width = n.trailing_zeros() as usize)
+            }
+            _ => k.to_string(),
+        };
+
+        match self.shape {
+            (1, 1) => write!(f, "{}", self.data[0]),
+            (_, 1) => self.write_vector(f, |k| format!("|{}>", label(k))),
+            (1, _) => self.write_vector(f, |k| format!("<{}|", label(k))),
+            (m, n) => {
+                for i in 0..m {
+                    for j in 0..n {
+                        match j {
+                            0 => write!(f, "{}", self.data[i * n + j])?,
+                            _ => write!(f, ", {}", self.data[i * n + j])?,
+                        }
+                    }
+
+                    if i < m - 1 {
+                        writeln!(f)?;
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ExactTensor {
+    fn write_vector<L: Fn(usize) -> String>(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        label: L,
+    ) -> fmt::Result {
+        let amplitudes: Vec<(usize, &Exact)> = self
+            .data
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| !x.is_zero())
+            .collect();
+
+        if amplitudes.is_empty() {
+            return write!(f, "0");
+        }
+
+        // The factor of one of the amplitudes that leaves all of them with
+        // integer coefficients, if any
+        let (factor, inverse) = amplitudes
+            .iter()
+            .filter_map(|(_, x)| {
+                let factor = x.scale_factor();
+                let inverse = factor.inv()?;
+
+                amplitudes
+                    .iter()
+                    .all(|(_, y)| (*y * &inverse).has_integer_coefficients())
+                    .then_some((factor, inverse))
+            })
+            .next()
+            .unwrap_or_else(|| (Exact::integer(1), Exact::integer(1)));
+
+        let mut sum = String::new();
+        for (k, x) in amplitudes.iter() {
+            let x = *x * &inverse;
+            let coefficient = if x == Exact::integer(1) {
+                String::new()
+            } else if x == Exact::integer(-1) {
+                "-".to_string()
+            } else if x.is_monomial() {
+                x.to_string()
+            } else {
+                format!("({})", x)
+            };
+
+            let term = format!("{}{}", coefficient, label(*k));
+            match (sum.is_empty(), term.strip_prefix('-')) {
+                (true, _) => sum = term,
+                (false, Some(term)) => sum = format!("{} - {}", sum, term),
+                (false, None) => sum = format!("{} + {}", sum, term),
+            }
+        }
+
+        if factor == Exact::integer(1) {
+            return write!(f, "{}", sum);
+        }
+        if amplitudes.len() > 1 {
+            sum = format!("({})", sum);
+        }
+
+        // Factors such as 1/√2 are written as divisions
+        match inverse.has_integer_coefficients() {
+            true => write!(f, "{}/{}", sum, inverse),
+            false => write!(f, "{}{}", factor, sum),
+        }
+    }
+}
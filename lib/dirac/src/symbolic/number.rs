@@ -0,0 +1,402 @@
+//! Exact complex numbers built from rationals, square roots and roots of
+//! unity.
+
+use num::complex::Complex64;
+use num::rational::Rational64;
+use num::{BigInt, BigRational, Integer, One, Signed, ToPrimitive, Zero};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::ops::{Add, Mul, Neg, Sub};
+
+// Largest number whose square root is taken exactly, keeping the trial
+// division that extracts its square factors cheap
+const MAX_RADICAND: u64 = 1 << 40;
+
+// Largest denominator recognised in float literals. Closer rational
+// approximations of a float are rounding artifacts rather than the value that
+// was meant, as for 0.7071067811865475.
+const MAX_DENOMINATOR: i64 = 1_000_000;
+
+// Largest exponent and coefficient size, in bits, of exact powers. Beyond
+// them, powers are left to the floating point evaluation rather than holding
+// up macro expansion with huge integers.
+pub const MAX_EXPONENT: u64 = 1 << 16;
+pub const MAX_BITS: u64 = 1 << 10;
+
+/// An exact complex number: a sum of terms q √r e^{iπθ} with rational q,
+/// square-free r and rational θ in [0, 1). Roots of unity of order 4, 6, 8
+/// and 12 are written with radicals instead, θ being 0, 1/2 (the imaginary
+/// unit) or a fraction with another denominator, so that the usual
+/// amplitudes have a unique representation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exact {
+    // Coefficient of each (phase, radicand) pair, none of them zero. Phases
+    // are arbitrary precision so that products of roots of unity of large
+    // coprime orders cannot overflow.
+    terms: BTreeMap<(BigRational, BigInt), BigRational>,
+}
+
+impl Exact {
+    pub fn zero() -> Exact {
+        Exact {
+            terms: BTreeMap::new(),
+        }
+    }
+
+    pub fn rational(q: BigRational) -> Exact {
+        Self::single(q, BigInt::one(), BigRational::zero())
+    }
+
+    pub fn integer(n: i64) -> Exact {
+        Self::rational(BigRational::from_integer(n.into()))
+    }
+
+    pub fn i() -> Exact {
+        Self::root_of_unity(half())
+    }
+
+    // e^{iπθ}
+    pub fn root_of_unity(theta: BigRational) -> Exact {
+        Self::term(BigRational::one(), BigInt::one(), theta)
+    }
+
+    // Recovers the exact value of a float literal such as 0.5 or 0.1 as the
+    // simplest rational that rounds to it
+    pub fn from_complex(c: Complex64) -> Option<Exact> {
+        let rational = |x: f64| {
+            Rational64::approximate_float(x)
+                .filter(|q| *q.denom() <= MAX_DENOMINATOR && q.to_f64() == Some(x))
+                .map(|q| BigRational::new((*q.numer()).into(), (*q.denom()).into()))
+        };
+
+        Some(&Self::rational(rational(c.re)?) + &(&Self::i() * &Self::rational(rational(c.im)?)))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    // The terms q √r e^{iπθ} as (q, r, θ), real terms first
+    pub fn terms(&self) -> impl Iterator<Item = (&BigRational, &BigInt, &BigRational)> {
+        self.terms.iter().map(|((theta, r), q)| (q, r, theta))
+    }
+
+    // Whether the number is a single term q √r e^{iπθ}
+    pub fn is_monomial(&self) -> bool {
+        self.terms.len() == 1
+    }
+
+    pub fn has_integer_coefficients(&self) -> bool {
+        self.terms.values().all(|q| q.is_integer())
+    }
+
+    // The positive real factor |q| √r of a single term, or 1 for sums. Used to
+    // pull a common factor out of the amplitudes of a state.
+    pub fn scale_factor(&self) -> Exact {
+        match self.terms.iter().collect::<Vec<_>>()[..] {
+            [((_, r), q)] => Self::single(q.abs(), r.clone(), BigRational::zero()),
+            _ => Self::integer(1),
+        }
+    }
+
+    // The value as a rational number, if it is one
+    pub fn to_rational(&self) -> Option<BigRational> {
+        match self.terms.iter().collect::<Vec<_>>()[..] {
+            [] => Some(BigRational::zero()),
+            [((theta, r), q)] if theta.is_zero() && r.is_one() => Some(q.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn to_complex(&self) -> Complex64 {
+        self.terms
+            .iter()
+            .map(|((theta, r), q)| {
+                let modulus = q.to_f64().unwrap() * r.to_f64().unwrap().sqrt();
+
+                match theta {
+                    _ if theta.is_zero() => Complex64::new(modulus, 0.0),
+                    _ if *theta == half() => Complex64::new(0.0, modulus),
+                    _ => Complex64::from_polar(
+                        modulus,
+                        std::f64::consts::PI * theta.to_f64().unwrap(),
+                    ),
+                }
+            })
+            .sum()
+    }
+
+    pub fn conj(&self) -> Exact {
+        self.terms
+            .iter()
+            .map(|((theta, r), q)| Self::term(q.clone(), r.clone(), -theta.clone()))
+            .fold(Self::zero(), |sum, term| &sum + &term)
+    }
+
+    pub fn re(&self) -> Exact {
+        &(self + &self.conj()) * &Self::rational(BigRational::new(1.into(), 2.into()))
+    }
+
+    pub fn im(&self) -> Exact {
+        &(self - &self.conj())
+            * &Self::term(
+                BigRational::new((-1).into(), 2.into()),
+                BigInt::one(),
+                half(),
+            )
+    }
+
+    // Squared modulus |z|^2 = z z*
+    pub fn norm_sqr(&self) -> Exact {
+        self * &self.conj()
+    }
+
+    // Square root of a rational number, extracting square factors out of the
+    // radical: √(-8/3) = 2i√6/3
+    pub fn sqrt(&self) -> Option<Exact> {
+        let q = self.to_rational()?;
+
+        if q.is_zero() {
+            return Some(Self::zero());
+        }
+
+        let n = (q.numer().abs() * q.denom())
+            .to_u64()
+            .filter(|&n| n <= MAX_RADICAND)?;
+        let (root, radicand) = square_free(n);
+        let theta = match q.is_negative() {
+            true => half(),
+            false => BigRational::zero(),
+        };
+
+        Some(Self::term(
+            BigRational::new(root.into(), q.denom().clone()),
+            radicand.into(),
+            theta,
+        ))
+    }
+
+    // Inverse 1/z = z*/|z|^2, for numbers whose squared modulus is rational
+    pub fn inv(&self) -> Option<Exact> {
+        match self.norm_sqr().to_rational() {
+            Some(norm) if !norm.is_zero() => Some(&self.conj() * &Self::rational(norm.recip())),
+            _ => None,
+        }
+    }
+
+    // Integer power by repeated squaring, with negative exponents going
+    // through the inverse
+    pub fn powi(&self, n: i64) -> Option<Exact> {
+        if n.unsigned_abs() > MAX_EXPONENT {
+            return None;
+        }
+
+        let mut base = match n < 0 {
+            true => self.inv()?,
+            false => self.clone(),
+        };
+        let mut power = Self::integer(1);
+        let mut n = n.unsigned_abs();
+
+        while n > 0 {
+            if n & 1 == 1 {
+                power = &power * &base;
+            }
+            n >>= 1;
+            if n > 0 {
+                base = &base * &base;
+            }
+            if power.bits() > MAX_BITS || base.bits() > MAX_BITS {
+                return None;
+            }
+        }
+
+        Some(power)
+    }
+
+    // Size in bits of the largest numerator or denominator of the coefficients
+    pub fn bits(&self) -> u64 {
+        self.terms
+            .values()
+            .map(|q| q.numer().bits().max(q.denom().bits()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    // The single term q √r e^{iπθ} with no further canonicalisation
+    fn single(q: BigRational, r: BigInt, theta: BigRational) -> Exact {
+        let mut number = Self::zero();
+
+        if !q.is_zero() {
+            number.terms.insert((theta, r), q);
+        }
+
+        number
+    }
+
+    // The term q √r e^{iπθ} in canonical form
+    fn term(q: BigRational, r: BigInt, theta: BigRational) -> Exact {
+        let two = BigRational::from_integer(2.into());
+        let theta = &theta - (&theta / &two).floor() * &two;
+        let (q, theta) = match theta >= BigRational::one() {
+            true => (-q, theta - BigRational::one()),
+            false => (q, theta),
+        };
+
+        // cos πθ and sin πθ as a coefficient over 2 and a radicand
+        let (cos, sin) = match (theta.numer().to_i64(), theta.denom().to_i64()) {
+            (Some(1), Some(4)) => ((1, 2), (1, 2)),
+            (Some(3), Some(4)) => ((-1, 2), (1, 2)),
+            (Some(1), Some(3)) => ((1, 1), (1, 3)),
+            (Some(2), Some(3)) => ((-1, 1), (1, 3)),
+            (Some(1), Some(6)) => ((1, 3), (1, 1)),
+            (Some(5), Some(6)) => ((-1, 3), (1, 1)),
+            _ => return Self::single(q, r, theta),
+        };
+
+        let part = |(c, s): (i64, u64), theta| {
+            let (root, radicand) = radical_product(&r, &s.into());
+
+            Self::single(&q * BigRational::new(root * c, 2.into()), radicand, theta)
+        };
+
+        &part(cos, BigRational::zero()) + &part(sin, half())
+    }
+}
+
+// The phase θ = 1/2 of the imaginary unit
+fn half() -> BigRational {
+    BigRational::new(1.into(), 2.into())
+}
+
+// Writes n as k² s with s square-free, returning (k, s)
+fn square_free(mut n: u64) -> (u64, u64) {
+    let (mut root, mut radicand) = (1, 1);
+    let mut p = 2;
+
+    while p * p <= n {
+        while n.is_multiple_of(p * p) {
+            n /= p * p;
+            root *= p;
+        }
+        if n.is_multiple_of(p) {
+            n /= p;
+            radicand *= p;
+        }
+        p += 1;
+    }
+
+    (root, radicand * n)
+}
+
+// √a √b = k √s for square-free a and b, returning (k, s)
+fn radical_product(a: &BigInt, b: &BigInt) -> (BigInt, BigInt) {
+    let g = a.gcd(b);
+    let s = (a / &g) * (b / &g);
+
+    (g, s)
+}
+
+impl Add for &Exact {
+    type Output = Exact;
+
+    fn add(self, rhs: &Exact) -> Exact {
+        let mut sum = self.clone();
+
+        for (key, q) in rhs.terms.iter() {
+            let total = sum.terms.remove(key).unwrap_or_else(BigRational::zero) + q;
+
+            if !total.is_zero() {
+                sum.terms.insert(key.clone(), total);
+            }
+        }
+
+        sum
+    }
+}
+
+impl Sub for &Exact {
+    type Output = Exact;
+
+    fn sub(self, rhs: &Exact) -> Exact {
+        self + &-rhs
+    }
+}
+
+impl Neg for &Exact {
+    type Output = Exact;
+
+    fn neg(self) -> Exact {
+        Exact {
+            terms: self
+                .terms
+                .iter()
+                .map(|(key, q)| (key.clone(), -q))
+                .collect(),
+        }
+    }
+}
+
+impl Mul for &Exact {
+    type Output = Exact;
+
+    fn mul(self, rhs: &Exact) -> Exact {
+        let mut product = Exact::zero();
+
+        for ((theta_a, a), p) in self.terms.iter() {
+            for ((theta_b, b), q) in rhs.terms.iter() {
+                let (root, radicand) = radical_product(a, b);
+                let term = Exact::term(
+                    p * q * BigRational::from_integer(root),
+                    radicand,
+                    theta_a + theta_b,
+                );
+
+                product = &product + &term;
+            }
+        }
+
+        product
+    }
+}
+
+impl Display for Exact {
+    // Terms are written as 3i√2/4 or √2e^{iπ/5}, real terms first
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        for (k, ((theta, r), q)) in self.terms.iter().enumerate() {
+            match (k, q.is_negative()) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+
+            let numerator = q.numer().abs();
+            let plain = theta.is_zero() && r.is_one();
+
+            if !numerator.is_one() || plain {
+                write!(f, "{}", numerator)?;
+            }
+            if *theta == half() {
+                write!(f, "i")?;
+            }
+            if !r.is_one() {
+                write!(f, "√{}", r)?;
+            }
+            match (theta.numer(), theta.denom()) {
+                _ if theta.is_zero() || *theta == half() => {}
+                (n, d) if n.is_one() => write!(f, "e^{{iπ/{}}}", d)?,
+                (n, d) => write!(f, "e^{{{}iπ/{}}}", n, d)?,
+            }
+            if !q.denom().is_one() {
+                write!(f, "/{}", q.denom())?;
+            }
+        }
+
+        Ok(())
+    }
+}
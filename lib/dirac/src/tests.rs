@@ -1,15 +1,59 @@
+use super::checker::Type;
+use super::codegen::{ToLatex, ToQasm, ToQuil};
+use super::expression::{Kind, Span};
 use super::parser;
-use super::tensor::Tensor;
+use super::qasm::qasm;
+use super::symbolic::ExactTensor;
+use super::tensor::{assert_operator_eq, assert_state_eq, Tensor};
+use super::{generate, unquote};
 use num::complex::Complex64;
 
-const EPSILON: f64 = 0.01;
-
+// Evaluates an expression, checking that its inferred type matches the result
+// and that optimizing it first and evaluating it exactly give the same result
 fn compute_tensor(expression: &str) -> Tensor {
-    parser::dirac(expression).unwrap().1.compute()
+    let tensor = parser::dirac(expression).unwrap().compute().unwrap();
+    let checked = parser::dirac(expression).unwrap().check().unwrap();
+
+    assert_eq!(checked, Type::from(tensor.shape));
+    let optimized = parser::dirac(expression)
+        .unwrap()
+        .optimize()
+        .compute()
+        .unwrap();
+
+    assert_operator_eq!(optimized, tensor);
+    if let Some(exact) = parser::dirac(expression).unwrap().exact() {
+        assert_operator_eq!(Tensor::from(&exact), tensor);
+    }
+    tensor
+}
+
+fn exact(expression: &str) -> ExactTensor {
+    parser::dirac(expression).unwrap().exact().unwrap()
+}
+
+// Message and span of the error the checker reports
+fn check_error(expression: &str) -> (String, Span) {
+    let error = parser::dirac(expression).unwrap().check().unwrap_err();
+
+    (error.message, error.span)
 }
 
-fn compute_complex(expression: &str) -> Complex64 {
-    compute_tensor(expression).item().unwrap()
+// Message and span of the error reported when evaluating a checked expression
+fn compute_error(expression: &str) -> (String, Span) {
+    let expression = parser::dirac(expression).unwrap();
+    expression.check().unwrap();
+    let error = expression.compute().unwrap_err();
+
+    (error.message, error.span)
+}
+
+fn optimize(expression: &str) -> Kind {
+    parser::dirac(expression).unwrap().optimize().kind
+}
+
+fn scalar(c: Complex64) -> Tensor {
+    Tensor::new(vec![c], (1, 1))
 }
 
 macro_rules! c {
@@ -23,50 +67,993 @@ macro_rules! c {
 
 #[test]
 fn computation() {
-    assert!((compute_complex("3(3)") - c![9.0]).norm() < EPSILON);
-    assert!((compute_complex("2 + 2") - c![4.0]).norm() < EPSILON);
-    assert!((compute_complex("2 / 2") - c!(1.0, 0.0)).norm() < EPSILON);
-    assert!((compute_complex("(2 + 2) * 3") - c!(12.0, 0.0)).norm() < EPSILON);
-    assert!((compute_complex("2 + 2 * 3") - c!(8.0, 0.0)).norm() < EPSILON);
-    assert!((compute_complex("2 + 1i") - c!(2.0, 1.0)).norm() < EPSILON);
-    assert!((compute_complex("1i + 2") - c!(2.0, 1.0)).norm() < EPSILON);
-    assert!((compute_complex("3 * (1 + i)") - c!(3.0, 3.0)).norm() < EPSILON);
-    assert!((compute_complex("3 * 1+i") - c!(3.0, 1.0)).norm() < EPSILON);
-
-    assert!((compute_tensor("|0>") - Tensor::new(vec![c![1.0], c![0.0]], (2, 1))).norm() < EPSILON);
-    assert!((compute_tensor("|1>") - Tensor::new(vec![c![0.0], c![1.0]], (2, 1))).norm() < EPSILON);
-
-    assert!((compute_complex("<0|0>") - c![1.0]).norm() < EPSILON);
-    assert!((compute_complex("<0|1>") - c![0.0]).norm() < EPSILON);
+    assert_operator_eq!(compute_tensor("3(3)"), scalar(c![9.0]));
+    assert_operator_eq!(compute_tensor("2 + 2"), scalar(c![4.0]));
+    assert_operator_eq!(compute_tensor("2 / 2"), scalar(c!(1.0, 0.0)));
+    assert_operator_eq!(compute_tensor("(2 + 2) * 3"), scalar(c!(12.0, 0.0)));
+    assert_operator_eq!(compute_tensor("2 + 2 * 3"), scalar(c!(8.0, 0.0)));
+    assert_operator_eq!(compute_tensor("2 + 1i"), scalar(c!(2.0, 1.0)));
+    assert_operator_eq!(compute_tensor("1i + 2"), scalar(c!(2.0, 1.0)));
+    assert_operator_eq!(compute_tensor("3 * (1 + i)"), scalar(c!(3.0, 3.0)));
+    assert_operator_eq!(compute_tensor("3 * 1+i"), scalar(c!(3.0, 1.0)));
 
-    assert!(
-        (compute_tensor("|1><1|") - Tensor::new(vec![c![0.0], c!(0.0), c!(0.0), c![1.0]], (2, 2)))
-            .norm()
-            < EPSILON
+    assert_state_eq!(
+        compute_tensor("|0>"),
+        Tensor::new(vec![c![1.0], c![0.0]], (2, 1))
+    );
+    assert_state_eq!(
+        compute_tensor("|1>"),
+        Tensor::new(vec![c![0.0], c![1.0]], (2, 1))
     );
 
-    assert!(
-        (compute_tensor("|0><0|") - Tensor::new(vec![c![1.0], c!(0.0), c!(0.0), c!(0.0)], (2, 2)))
-            .norm()
-            < EPSILON
+    assert_operator_eq!(compute_tensor("<0|0>"), scalar(c![1.0]));
+    assert_operator_eq!(compute_tensor("<0|1>"), scalar(c![0.0]));
+
+    assert_operator_eq!(
+        compute_tensor("|1><1|"),
+        Tensor::new(vec![c![0.0], c!(0.0), c!(0.0), c![1.0]], (2, 2))
     );
 
-    assert!(
-        (compute_tensor("|0>3") - Tensor::new(vec![c!(3.0), c!(0.0)], (2, 1))).norm() < EPSILON
+    assert_operator_eq!(
+        compute_tensor("|0><0|"),
+        Tensor::new(vec![c![1.0], c!(0.0), c!(0.0), c!(0.0)], (2, 2))
+    );
+
+    assert_state_eq!(
+        compute_tensor("|0>3"),
+        Tensor::new(vec![c!(3.0), c!(0.0)], (2, 1))
+    );
+    assert_state_eq!(
+        compute_tensor("3|0>"),
+        Tensor::new(vec![c!(3.0), c!(0.0)], (2, 1))
+    );
+
+    assert_state_eq!(compute_tensor("|1> x |0>"), compute_tensor("|10>"));
+
+    assert_operator_eq!(compute_tensor("|0>'"), compute_tensor("<0|"));
+    assert_state_eq!(compute_tensor("|0>"), compute_tensor("<0|'"));
+
+    assert_operator_eq!(compute_tensor("||1>|"), scalar(c![1.0]));
+    assert_operator_eq!(compute_tensor("||1>|"), compute_tensor("|<1||"));
+
+    let zero_bra = Tensor::new(vec![c![0.0]; 2], (1, 2));
+    let zero_ket = Tensor::new(vec![c![0.0]; 2], (2, 1));
+    assert_operator_eq!(compute_tensor("|0>' - <0|"), zero_bra);
+    assert_operator_eq!(compute_tensor("|0> - <0|'"), zero_ket);
+    assert_operator_eq!(compute_tensor("<0|' - |0>"), zero_ket);
+    assert_operator_eq!(compute_tensor("<0| - |0>'"), zero_bra);
+    assert_operator_eq!(compute_tensor("||1>| - |<1||"), scalar(c![0.0]));
+}
+
+#[test]
+fn power() {
+    assert_operator_eq!(compute_tensor("2^3"), scalar(c![8.0]));
+    assert_operator_eq!(compute_tensor("-2^2"), scalar(c![-4.0]));
+    assert_operator_eq!(compute_tensor("2^-1"), scalar(c![0.5]));
+    assert_operator_eq!(compute_tensor("2^3^2"), scalar(c![512.0]));
+    assert_operator_eq!(compute_tensor("4^0.5"), scalar(c![2.0]));
+    assert_operator_eq!(compute_tensor("i^2"), scalar(c![-1.0]));
+
+    assert_operator_eq!(compute_tensor("(|0><1| + |1><0|)^2"), Tensor::eye(2));
+    assert_operator_eq!(
+        compute_tensor("(|0><1| + |1><0|)^3"),
+        compute_tensor("|0><1| + |1><0|")
+    );
+}
+
+#[test]
+fn functions() {
+    assert_operator_eq!(compute_tensor("sqrt(4)"), scalar(c![2.0]));
+    assert_operator_eq!(compute_tensor("sqrt(-1)"), scalar(c!(0.0, 1.0)));
+    assert_operator_eq!(compute_tensor("exp(i*pi)"), scalar(c![-1.0]));
+    assert_operator_eq!(compute_tensor("ln(e)"), scalar(c![1.0]));
+    assert_operator_eq!(compute_tensor("sin(pi/2) + cos(pi)"), scalar(c![0.0]));
+    assert_operator_eq!(compute_tensor("abs(3 + 4i)"), scalar(c![5.0]));
+    assert_operator_eq!(compute_tensor("conj(3 + 4i)"), scalar(c!(3.0, -4.0)));
+    assert_operator_eq!(compute_tensor("re(3 + 4i) + im(3 + 4i)"), scalar(c![7.0]));
+
+    assert_state_eq!(
+        compute_tensor("1/sqrt(2) (|0> + |1>)"),
+        compute_tensor("(|0> + |1>) / ||0> + |1>|")
+    );
+}
+
+#[test]
+fn operator_functions() {
+    // exp(-iθ X⊗X) = cos(θ) I - i sin(θ) X⊗X
+    let theta = std::f64::consts::FRAC_PI_4;
+    let expected =
+        &Tensor::eye(4) * c![theta.cos()] + &compute_tensor("X x X") * c!(0.0, -theta.sin());
+    assert_operator_eq!(compute_tensor("exp(-i * pi/4 * X x X)"), expected);
+
+    assert_operator_eq!(compute_tensor("exp(i * pi * Z)"), compute_tensor("-I"));
+    assert_operator_eq!(compute_tensor("sqrt(4 I)"), compute_tensor("2 I"));
+    assert_operator_eq!(
+        compute_tensor("sqrt(2 I x I + X x X)^2"),
+        compute_tensor("2 I x I + X x X")
+    );
+    assert_operator_eq!(
+        compute_tensor("exp(ln(2 I + X))"),
+        compute_tensor("2 I + X")
+    );
+    assert_operator_eq!(compute_tensor("sin(Z)^2 + cos(Z)^2"), Tensor::eye(2));
+    assert_operator_eq!(compute_tensor("H H"), Tensor::eye(2));
+    assert_operator_eq!(compute_tensor("X Y"), compute_tensor("i Z"));
+
+    // Singular non-normal operators have no principal square root
+    assert_eq!(
+        compute_error("sqrt(|0><1|)"),
+        (
+            "cannot take the square root: matrix is singular".to_string(),
+            Span::new(0, 12)
+        )
+    );
+    assert_eq!(
+        compute_error("ln(|0><1|)").0,
+        "cannot take the logarithm: matrix is singular"
+    );
+    assert!(compute_error("ln(-I + 0.5 |0><1|)")
+        .0
+        .starts_with("cannot take the logarithm"));
+}
+
+#[test]
+fn information() {
+    let bell = "(|00> + |11>) / sqrt(2)";
+    let f = |expression: String| compute_tensor(&expression);
+
+    assert_operator_eq!(f(format!("entropy(ptrace({}, 1))", bell)), scalar(c![1.0]));
+    assert_operator_eq!(f(format!("entropy({})", bell)), scalar(c![0.0]));
+    assert_operator_eq!(f(format!("renyi(ptrace({}, 0), 2)", bell)), scalar(c![1.0]));
+    assert_operator_eq!(f(format!("concurrence({})", bell)), scalar(c![1.0]));
+    assert_operator_eq!(f(format!("negativity({}, 1)", bell)), scalar(c![0.5]));
+    assert_operator_eq!(f(format!("mutual_info({}, 0)", bell)), scalar(c![2.0]));
+    assert_operator_eq!(compute_tensor("concurrence(|0+>)"), scalar(c![0.0]));
+
+    assert_operator_eq!(compute_tensor("fidelity(|0>, |+>)"), scalar(c![0.5]));
+    assert_operator_eq!(compute_tensor("fidelity(|0><0|, |0>)"), scalar(c![1.0]));
+    assert_operator_eq!(
+        compute_tensor("trace_distance(|0>, |1><1|)"),
+        scalar(c![1.0])
+    );
+
+    assert_operator_eq!(
+        compute_tensor("ptrace(|0> x |1>, 0)"),
+        compute_tensor("|1><1|")
+    );
+    assert_operator_eq!(compute_tensor("entropy(I / 2)"), scalar(c![1.0]));
+
+    // Operators that are not density operators, and unnormalized kets
+    let message = |name| format!("`{}` expects a normalized ket or a density operator", name);
+    assert_eq!(
+        compute_error("entropy(2 |0><1|)"),
+        (message("entropy"), Span::new(0, 17))
+    );
+    assert_eq!(compute_error("entropy(X)").0, message("entropy"));
+    assert_eq!(compute_error("entropy(2 |0><0|)").0, message("entropy"));
+    assert_eq!(compute_error("fidelity(X, X)").0, message("fidelity"));
+    assert_eq!(compute_error("fidelity(|0>, 2 |0>)").0, message("fidelity"));
+    assert_eq!(
+        compute_error("trace_distance(|0>, |0><1| + |1><0|)").0,
+        message("trace_distance")
+    );
+    assert_eq!(compute_error("renyi(|0> + |1>, 2)").0, message("renyi"));
+    assert_eq!(
+        compute_error("negativity(X x X, 0)").0,
+        message("negativity")
+    );
+    assert_eq!(
+        compute_error("mutual_info(Z x Z, 0)").0,
+        message("mutual_info")
+    );
+    assert_eq!(
+        compute_error("concurrence(I x I)").0,
+        message("concurrence")
+    );
+}
+
+#[test]
+fn partial_transpose() {
+    assert_operator_eq!(
+        compute_tensor("pt(|10><01|, 1)"),
+        compute_tensor("|11><00|")
+    );
+    assert_operator_eq!(
+        compute_tensor("pt(|10><01|, 0)"),
+        compute_tensor("|00><11|")
     );
+    assert_operator_eq!(
+        compute_tensor("pt(pt(|+0>, 0), 0)"),
+        compute_tensor("|+0><+0|")
+    );
+}
+
+#[test]
+fn pauli_strings() {
+    assert_operator_eq!(compute_tensor("Z0 Z1"), compute_tensor("Z x Z"));
+    assert_operator_eq!(
+        compute_tensor("Z0 Z1 + 0.5 X2"),
+        compute_tensor("Z x Z x I + 0.5 I x I x X")
+    );
+    assert_operator_eq!(compute_tensor("X0 Y0"), compute_tensor("i Z"));
+    assert_state_eq!(compute_tensor("Z1 |00>"), compute_tensor("|00>"));
+    assert_state_eq!(compute_tensor("X1 |00>"), compute_tensor("|01>"));
+
+    // Pauli strings are as wide as the states they are applied to
+    assert_state_eq!(compute_tensor("X0 |00>"), compute_tensor("|10>"));
+    assert_operator_eq!(compute_tensor("<000| Z0 X2"), compute_tensor("<001|"));
+    assert_eq!(
+        parser::dirac("X0 |00>").unwrap().check().unwrap(),
+        Type::Ket(4)
+    );
+    assert_eq!(
+        check_error("X2 |00>").0,
+        "cannot multiply an operator of shape 8x8 by a ket of dimension 4"
+    );
+
+    // Transverse field Ising model on 3 qubits
+    assert_operator_eq!(
+        compute_tensor("n = 3; sum_{i=0}^{n-2} Z_i Z_{i+1} + 0.5 sum_{i=0}^{n-1} X_i"),
+        compute_tensor("Z x Z x I + I x Z x Z + 0.5 (X x I x I + I x X x I + I x I x X)")
+    );
+
+    // Heisenberg chain
+    assert_operator_eq!(
+        compute_tensor("sum_{i=0}^{1} (X_i X_{i+1} + Y_i Y_{i+1} + Z_i Z_{i+1})"),
+        compute_tensor("X x X x I + Y x Y x I + Z x Z x I + I x X x X + I x Y x Y + I x Z x Z")
+    );
+
+    // All-to-all couplings with nested sums
+    assert_operator_eq!(
+        compute_tensor("sum_{i=0}^{1} sum_{j=i+1}^{2} Z_i Z_j"),
+        compute_tensor("Z0 Z1 + Z0 Z2 + Z1 Z2")
+    );
+}
+
+#[test]
+fn commutators() {
+    assert_operator_eq!(compute_tensor("[X, Y]"), compute_tensor("2i Z"));
+    assert_operator_eq!(compute_tensor("[Y, X]"), compute_tensor("-2i Z"));
+    assert_operator_eq!(compute_tensor("{X, Y}"), compute_tensor("0 I"));
+    assert_operator_eq!(compute_tensor("{X, X}"), compute_tensor("2 I"));
+    assert_operator_eq!(compute_tensor("[X0 X1, Z0 Z1]"), compute_tensor("0 I x I"));
+    assert_operator_eq!(
+        compute_tensor("{X0, Z0} + [Z0, Z1]"),
+        compute_tensor("0 I x I")
+    );
+    assert_operator_eq!(
+        compute_tensor("[|0><1|, |1><0|]"),
+        compute_tensor("|0><0| - |1><1|")
+    );
+}
+
+#[test]
+fn linear_algebra() {
+    assert_operator_eq!(compute_tensor("tr(X x X + 2 I x I)"), scalar(c![8.0]));
+    assert_operator_eq!(compute_tensor("det(2 I + X)"), scalar(c![3.0]));
+    assert_operator_eq!(compute_tensor("det(|0><0|)"), scalar(c![0.0]));
+    assert_operator_eq!(compute_tensor("rank(|0><0| + |1><1|)"), scalar(c![2.0]));
+    assert_operator_eq!(compute_tensor("rank(|00><00| + |01><00|)"), scalar(c![1.0]));
+
+    assert_operator_eq!(compute_tensor("inv(2 I + X) (2 I + X)"), Tensor::eye(2));
+    assert_operator_eq!(
+        compute_tensor("(2 I + X)^-1"),
+        compute_tensor("inv(2 I + X)")
+    );
+    assert_operator_eq!(compute_tensor("(2 I + X)^-2 (2 I + X)^2"), Tensor::eye(2));
+    assert_operator_eq!(compute_tensor("X^-2147483648"), Tensor::eye(2));
+    assert_eq!(
+        compute_error("X^3000000000"),
+        (
+            "integer exponent 3000000000 is out of range".to_string(),
+            Span::new(2, 12)
+        )
+    );
+    assert_eq!(
+        compute_error("2^-3000000000").0,
+        "integer exponent -3000000000 is out of range"
+    );
+    assert_operator_eq!(compute_tensor("(2 I + X) / (2 I + X)"), Tensor::eye(2));
+    assert_operator_eq!(compute_tensor("<0| / H"), compute_tensor("<0| H"));
+
+    // Singular matrices have no inverse
+    assert_eq!(
+        compute_error("2 inv(|0><0|)"),
+        (
+            "cannot invert: matrix is singular".to_string(),
+            Span::new(2, 13)
+        )
+    );
+    assert_eq!(
+        compute_error("X / (|0><0|)"),
+        (
+            "cannot divide by a matrix: matrix is singular".to_string(),
+            Span::new(0, 12)
+        )
+    );
+    assert_eq!(
+        compute_error("(X + I)^-2").0,
+        "cannot raise to a negative power: matrix is singular"
+    );
+    assert_operator_eq!(compute_tensor("det(X + I)"), scalar(c![0.0]));
+}
 
+#[test]
+fn normalize() {
+    assert_state_eq!(
+        compute_tensor("normalize(|0> + |1>)"),
+        compute_tensor("|+>")
+    );
+    assert_state_eq!(
+        compute_tensor("|0> - |1>, normalize"),
+        compute_tensor("(|0> - |1>) / sqrt(2)")
+    );
+    assert_operator_eq!(compute_tensor("normalize(3 + 4i)"), scalar(c!(0.6, 0.8)));
+    assert_operator_eq!(
+        compute_tensor("normalize(2 X)"),
+        &compute_tensor("X") * (1.0 / 2f64.sqrt())
+    );
+    assert_state_eq!(
+        compute_tensor("i |+>").canonical_phase(),
+        compute_tensor("|+>")
+    );
+
+    assert_eq!(
+        compute_error("normalize(0 |0>)"),
+        (
+            "cannot normalize a zero tensor".to_string(),
+            Span::new(0, 16)
+        )
+    );
+    assert_eq!(
+        compute_error("ptrace(|00>, 2)").0,
+        "qubit index must be an integer between 0 and 1"
+    );
+}
+
+#[test]
+fn global_phase() {
+    let plus = compute_tensor("|+>");
+
+    assert!(compute_tensor("i |+>").eq_up_to_global_phase(&plus, 1e-8, 1e-5));
+    assert!(compute_tensor("exp(i pi/3) |+>").eq_up_to_global_phase(&plus, 1e-8, 1e-5));
+    assert!(!compute_tensor("|->").eq_up_to_global_phase(&plus, 1e-8, 1e-5));
+    assert!(!compute_tensor("i |+>").approx_eq(&plus, 1e-8, 1e-5));
+}
+
+#[test]
+fn optimizer() {
+    assert!(matches!(optimize("2 (3 + i)"), Kind::Scalar(_)));
+    assert!(matches!(optimize("<0|0> x <1|1> x <+|->"), Kind::Scalar(_)));
+    assert!(matches!(
+        optimize("(<0| x <+|) (|0> x |->) (pi / 2)"),
+        Kind::Scalar(_)
+    ));
+    assert!(matches!(optimize("((|0>))"), Kind::Ket(ket) if ket == "0"));
+    assert!(matches!(optimize("|0> x (|1> x |+>)"), Kind::Ket(ket) if ket == "01+"));
+    assert!(matches!(optimize("(|0> x |1>)'"), Kind::Bra(bra) if bra == "01"));
     assert!(
-        (compute_tensor("3|0>") - Tensor::new(vec![c!(3.0), c!(0.0)], (2, 1))).norm() < EPSILON
+        matches!(optimize("|0><1| x |1><0|"), Kind::Outer(ket, bra) if ket == "01" && bra == "10")
+    );
+
+    // Scalars are pulled out of products and merged
+    match optimize("2 |0> x (3 X) / 4") {
+        Kind::Mul(c, expr) => {
+            assert!(matches!(c.kind, Kind::Scalar(c) if c == c![1.5]));
+            assert!(matches!(expr.kind, Kind::Kronecker(..)));
+        }
+        expr => panic!("scalar was not pulled out of {:?}", expr),
+    }
+    assert!(matches!(optimize("-(-X)"), Kind::Constant(_)));
+
+    // Operands that are not basis states are kept as they are
+    assert!(matches!(optimize("n = 1; sum_{i=0}^{n} 2"), Kind::Let(..)));
+    assert!(matches!(optimize("<0| X |1>"), Kind::Mul(..)));
+}
+
+#[test]
+fn exact_evaluation() {
+    assert_eq!(
+        exact("(|0> + |1>) / ||0> + |1>|").to_string(),
+        "(|0> + |1>)/√2"
+    );
+    assert_eq!(exact("1/sqrt(2) (|0> - i|1>)"), exact("|->"));
+    assert_eq!(
+        exact("(sqrt(3) |0> + |1>) / 2").to_string(),
+        "(√3|0> + |1>)/2"
+    );
+    assert_eq!(exact("|+>, normalize"), exact("|+>"));
+    assert_eq!(exact("sqrt(-8/3)").to_string(), "2i√6/3");
+    assert_eq!(exact("0.1 + 0.25i").to_string(), "1/10 + i/4");
+    assert_eq!(exact("cos(pi/4)").to_string(), "√2/2");
+    assert_eq!(exact("exp(2i pi/5)").to_string(), "e^{2iπ/5}");
+    assert_eq!(exact("H").to_string(), "√2/2, √2/2\n√2/2, -√2/2");
+
+    // Identities hold exactly rather than up to rounding
+    assert_eq!(exact("H H"), exact("I"));
+    assert_eq!(exact("H Z H"), exact("X"));
+    assert_eq!(exact("exp(i pi/4)^2"), exact("i"));
+    assert_eq!(exact("exp(i pi/3)^3"), exact("-1"));
+    assert_eq!(exact("exp(i pi/5)^10"), exact("1"));
+    assert_eq!(exact("sin(pi/3)^2 + cos(pi/3)^2"), exact("1"));
+    assert_eq!(exact("<+|->"), exact("(1 - i)/2"));
+    assert_eq!(exact("sqrt(2) sqrt(6)"), exact("2 sqrt(3)"));
+    assert_eq!(exact("(1 + i)^-1"), exact("(1 - i)/2"));
+    assert_eq!(exact("sqrt(0)").to_string(), "0");
+    assert_eq!(exact("||0> - |0>|").to_string(), "0");
+    assert_eq!(exact("sqrt(0) |0>"), exact("0 |0>"));
+    assert_eq!(
+        exact("n = 2; sum_{k=0}^{n-1} Z_k"),
+        exact("2 |00><00| - 2 |11><11|")
+    );
+
+    // Values without an exact representation fall back to floats
+    assert!(parser::dirac("ln(2)").unwrap().exact().is_none());
+    assert!(parser::dirac("exp(X)").unwrap().exact().is_none());
+    assert!(parser::dirac("pi").unwrap().exact().is_none());
+    assert!(parser::dirac("1/sqrt(0)").unwrap().exact().is_none());
+
+    // Powers are exact up to a size, past which floats take over quickly
+    assert_eq!(exact("2^64").to_string(), "18446744073709551616");
+    assert_eq!(exact("exp(i pi/5)^50000"), exact("1"));
+    assert_eq!(exact("(2 X)^20"), exact("1048576 I"));
+    for expression in ["2^100000", "2^1000000000", "X^100000000", "(2 X)^5000"] {
+        assert!(parser::dirac(expression).unwrap().exact().is_none());
+    }
+    assert_operator_eq!(compute_tensor("X^100000000"), compute_tensor("I"));
+
+    // Phases of large coprime orders add up without overflowing
+    let phases = "exp(i*pi/999983) * exp(i*pi/999979) * exp(i*pi/999961) * exp(i*pi/999953)";
+    assert!(exact(phases).item().unwrap().is_monomial());
+    assert_operator_eq!(
+        compute_tensor(phases),
+        scalar(Complex64::from_polar(
+            1.0,
+            std::f64::consts::PI
+                * (1.0 / 999983.0 + 1.0 / 999979.0 + 1.0 / 999961.0 + 1.0 / 999953.0)
+        ))
+    );
+    assert!(parser::dirac("normalize(0 |0>)").unwrap().exact().is_none());
+}
+
+#[test]
+fn checker() {
+    let check = |expression| parser::dirac(expression).unwrap().check().unwrap();
+
+    assert_eq!(check("|0> x |+>"), Type::Ket(4));
+    assert_eq!(check("<01|"), Type::Bra(4));
+    assert_eq!(check("|0><10| + |1><01|"), Type::Operator(2, 4));
+    assert_eq!(check("<0| X |1>"), Type::Scalar);
+    assert_eq!(check("Z0 Z2"), Type::Operator(8, 8));
+    assert_eq!(check("ptrace(|00> + |11>, 0)"), Type::Operator(2, 2));
+    assert_eq!(check("ptrace(|0>, 0) + 1"), Type::Scalar);
+    assert_operator_eq!(compute_tensor("ptrace(|0>, 0) + 1"), scalar(c![2.0]));
+    assert_eq!(check("n = 2; sum_{i=0}^{n} X_i"), Type::Operator(8, 8));
+    assert_eq!(check("1/X"), Type::Operator(2, 2));
+    assert_eq!(check("2i/(H x X)"), Type::Operator(4, 4));
+    assert_operator_eq!(compute_tensor("1/X"), compute_tensor("X"));
+    assert_operator_eq!(compute_tensor("2/(2 H)"), compute_tensor("H"));
+
+    assert_eq!(
+        check_error("|0> + (|00> + |11>)"),
+        (
+            "cannot add a ket of dimension 4 to a ket of dimension 2".to_string(),
+            Span::new(0, 19)
+        )
+    );
+    assert_eq!(
+        check_error("1/|0>").0,
+        "cannot divide a scalar by a ket of dimension 2"
+    );
+    assert_eq!(
+        check_error("2 + <0| <1|").0,
+        "cannot multiply a bra of dimension 2 by a bra of dimension 2, use x for the Kronecker product"
+    );
+    assert_eq!(
+        check_error("X^|0>"),
+        (
+            "exponent must be a scalar, got a ket of dimension 2".to_string(),
+            Span::new(2, 5)
+        )
+    );
+    assert_eq!(
+        check_error("<0|0> + tr(|0>)").0,
+        "`tr` expects a square operator, got a ket of dimension 2"
+    );
+    assert_eq!(
+        check_error("[X, |0>]").0,
+        "cannot take the commutator of an operator of shape 2x2 and a ket of dimension 2"
+    );
+    assert_eq!(
+        check_error("concurrence(|000>)").0,
+        "`concurrence` expects a two qubit state, got a ket of dimension 8"
+    );
+    assert_eq!(check_error("X_n").0, "unbound variable `n`");
+    assert_eq!(
+        check_error("sum_{i=1}^{0} X_i").0,
+        "empty sum over i from 1 to 0"
+    );
+    assert_eq!(
+        check_error("sum_{i=0}^{1000000000} 1"),
+        (
+            "sum over i from 0 to 1000000000 has more than 65536 terms".to_string(),
+            Span::new(0, 24)
+        )
+    );
+    assert_eq!(check("sum_{i=1}^{65536} 1"), Type::Scalar);
+
+    // Dimensions that do not fit in a usize
+    let wide = format!("|{}>", "0".repeat(64));
+    assert_eq!(
+        check_error(&wide),
+        ("register too wide: 64 qubits".to_string(), Span::new(0, 66))
+    );
+    assert_eq!(
+        check_error("Z70"),
+        ("register too wide: 71 qubits".to_string(), Span::new(0, 3))
+    );
+    let halves = format!("|{0}> x |{0}>", "0".repeat(32));
+    assert_eq!(
+        check_error(&halves).0,
+        "register too wide for a Kronecker product"
+    );
+}
+
+#[test]
+fn kronecker_operators() {
+    let tensor = compute_tensor("(H x X x Z) |0+1>");
+    assert_state_eq!(tensor, compute_tensor("(H|0>) x (X|+>) x (Z|1>)"));
+    assert_operator_eq!(
+        compute_tensor("<01| (X x Y)' (H x H)"),
+        compute_tensor("(<01| (X x Y)') (H x H)")
+    );
+    assert_operator_eq!(
+        compute_tensor("-2 (X x Z) (Y x Y) + (X x Y)'"),
+        compute_tensor("-2 (X Y) x (Z Y) + X x Y'")
+    );
+
+    // Applied factor by factor, without building the 65536x65536 matrix
+    let hadamards = vec!["H"; 16].join(" x ");
+    let state = parser::dirac(&format!("({}) |{}>", hadamards, "0".repeat(16)))
+        .unwrap()
+        .compute()
+        .unwrap();
+    assert_eq!(state.shape, (1 << 16, 1));
+    assert!(state
+        .data
+        .iter()
+        .all(|c| (c - Complex64::new(1.0 / 256.0, 0.0)).norm() < 1e-12));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialization() {
+    use super::expression::Expression;
+
+    // Parsed programs survive a round trip, spans and all
+    for input in [
+        "n = 3; sum_{i=0}^{n-1} 0.5i X_i Z_{i+1}",
+        "exp(-i * pi/4 * X x X) |0+> / ||01>|",
+        "tr([H, <0|1>'] ^ 2)",
+    ] {
+        let expression = parser::dirac(input).unwrap();
+        let json = serde_json::to_string(&expression).unwrap();
+        let decoded: Expression = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", expression));
+        assert_operator_eq!(decoded.compute().unwrap(), expression.compute().unwrap());
+    }
+}
+
+// State prepared by an OpenQASM program, and the unitary of its circuit
+fn simulate(program: &str) -> (Tensor, Tensor) {
+    let circuit = qasm(program).unwrap();
+    let (state, unitary) = (circuit.clone().state(), circuit.unitary());
+
+    assert!(state.check().is_ok() && unitary.check().is_ok());
+    (state.compute().unwrap(), unitary.compute().unwrap())
+}
+
+#[test]
+fn qasm_circuits() {
+    let bell = "
+        OPENQASM 2.0;
+        include \"qelib1.inc\";
+        qreg q[2];
+        creg c[2];
+        h q[0]; // first qubit
+        /* entangle */ cx q[0], q[1];
+        barrier q;
+    ";
+    let (state, unitary) = simulate(bell);
+    assert_state_eq!(state, compute_tensor("(|00> + |11>)/sqrt(2)"));
+    assert_operator_eq!(unitary, compute_tensor("(|0><0| x I + |1><1| x X) (H x I)"));
+    assert_eq!(
+        qasm(bell).unwrap().state().exact(),
+        Some(exact("(|00> + |11>)/sqrt(2)"))
+    );
+
+    // q[0] is the leftmost factor, and registers follow each other
+    assert_state_eq!(
+        simulate("qreg q[2]; x q[0]; cx q[0], q[1];").0,
+        compute_tensor("|11>")
+    );
+    assert_state_eq!(
+        simulate("qreg q[2]; x q[1]; cx q[0], q[1];").0,
+        compute_tensor("|01>")
+    );
+    assert_state_eq!(
+        simulate("qreg a[1]; qreg b[2]; x b[1];").0,
+        compute_tensor("|001>")
+    );
+    assert_state_eq!(
+        simulate("OPENQASM 3; qubit[2] q; qubit r; x r;").0,
+        compute_tensor("|001>")
+    );
+
+    // Gates on whole registers apply to each qubit, or pair them up
+    assert_state_eq!(simulate("qreg q[3]; x q;").0, compute_tensor("|111>"));
+    assert_state_eq!(
+        simulate("qreg a[2]; qreg b[2]; x a[1]; cx a, b;").0,
+        compute_tensor("|0101>")
+    );
+
+    // Multi-qubit gates on qubits that are not adjacent
+    assert_state_eq!(
+        simulate("qreg q[3]; x q[0]; cz q[0], q[2]; h q[2]; cy q[0], q[2];").0,
+        compute_tensor("|10> x (Y H |0>)")
+    );
+    assert_state_eq!(
+        simulate("qreg q[3]; x q[0]; swap q[0], q[2];").0,
+        compute_tensor("|001>")
+    );
+    assert_state_eq!(
+        simulate("qreg q[3]; x q[0]; x q[2]; ccx q[0], q[2], q[1];").0,
+        compute_tensor("|111>")
+    );
+    assert_state_eq!(
+        simulate("qreg q[3]; x q[0]; x q[2]; ccx q[0], q[1], q[2];").0,
+        compute_tensor("|101>")
+    );
+    assert_state_eq!(
+        simulate("qreg q[3]; x q[0]; x q[2]; cswap q[0], q[1], q[2];").0,
+        compute_tensor("|110>")
+    );
+    assert_state_eq!(
+        simulate("qreg q[3]; x q[1]; cswap q[0], q[1], q[2];").0,
+        compute_tensor("|010>")
+    );
+}
+
+#[test]
+fn qasm_gates() {
+    let gate = |program: &str| simulate(&format!("qreg q[1]; {}", program)).1;
+
+    assert_operator_eq!(gate("id q[0];"), compute_tensor("I"));
+    assert_operator_eq!(gate("s q[0]; s q[0];"), compute_tensor("Z"));
+    assert_operator_eq!(gate("t q[0]; t q[0];"), gate("s q[0];"));
+    assert_operator_eq!(
+        gate("sdg q[0]; s q[0]; tdg q[0]; t q[0];"),
+        compute_tensor("I")
+    );
+    assert_operator_eq!(gate("sx q[0]; sx q[0];"), compute_tensor("X"));
+    assert_operator_eq!(gate("sxdg q[0]; sx q[0];"), compute_tensor("I"));
+    assert_operator_eq!(gate("rx(0.3) q[0];"), compute_tensor("exp(-i * 0.15 * X)"));
+    assert_operator_eq!(gate("ry(0.3) q[0];"), compute_tensor("exp(-i * 0.15 * Y)"));
+    assert_operator_eq!(gate("rz(0.3) q[0];"), compute_tensor("exp(-i * 0.15 * Z)"));
+    assert_operator_eq!(
+        gate("p(0.3) q[0];"),
+        compute_tensor("|0><0| + exp(0.3i)|1><1|")
+    );
+    assert_operator_eq!(gate("u1(-pi/2) q[0];"), gate("sdg q[0];"));
+    assert_operator_eq!(gate("u2(0, pi) q[0];"), compute_tensor("H"));
+
+    // U(θ, φ, λ) = e^{i(φ+λ)/2} Rz(φ) Ry(θ) Rz(λ)
+    assert_operator_eq!(
+        gate("U(0.3, 0.5, 0.7) q[0];"),
+        &gate("rz(0.7) q[0]; ry(0.3) q[0]; rz(0.5) q[0];") * Complex64::new(0.0, 0.6).exp()
+    );
+
+    // Parameters are real arithmetic over numbers, π and τ
+    assert_operator_eq!(
+        gate("rz(-(pi/4) + 2 * π) q[0];"),
+        gate("rz(tau - pi/4) q[0];")
+    );
+    assert_operator_eq!(gate("rz(2.5e-1 * cos(0)) q[0];"), gate("rz(0.25) q[0];"));
+
+    // Controlled gates apply their base gate when the control is |1>
+    let controlled = |program: &str| simulate(&format!("qreg q[2]; {}", program)).1;
+    for (base, parameters) in [
+        ("h", ""),
+        ("rx", "(0.4)"),
+        ("p", "(1.1)"),
+        ("u3", "(1, 2, 3)"),
+    ] {
+        assert_operator_eq!(
+            controlled(&format!("c{}{} q[0], q[1];", base, parameters)),
+            &compute_tensor("|0><0| x I")
+                + &(&compute_tensor("|1><1|") * 1.0)
+                    .prod(&gate(&format!("{}{} q[0];", base, parameters)))
+        );
+    }
+}
+
+#[test]
+fn qasm_errors() {
+    let error = |program| {
+        let error = qasm(program).unwrap_err();
+
+        (error.message, error.span)
+    };
+
+    assert_eq!(
+        error("qreg q[2];\nfoo q[0];"),
+        ("unknown gate `foo`".to_string(), Span::new(11, 14))
+    );
+    assert_eq!(
+        error("qreg q[2]; cx q[0], q[2];"),
+        (
+            "qubit index 2 is out of range for register `q` of size 2".to_string(),
+            Span::new(20, 24)
+        )
+    );
+    assert_eq!(
+        error("qreg q[2]; h r[0];"),
+        ("unknown register `r`".to_string(), Span::new(13, 17))
+    );
+    assert_eq!(
+        error("qreg q[2]; h q[0]\nx q[1];"),
+        ("expected `;` after the gate".to_string(), Span::new(17, 17))
+    );
+    assert_eq!(
+        error("qreg q[2]; measure q[0];"),
+        (
+            "`measure` is not supported, only unitary circuits of standard gates are".to_string(),
+            Span::new(11, 18)
+        )
+    );
+    assert_eq!(
+        error("qreg q[1]; rz q[0];").0,
+        "`rz` takes 1 parameter(s) but 0 were given"
+    );
+    assert_eq!(
+        error("qreg q[2]; cx q[0];").0,
+        "`cx` acts on 2 qubit(s) but 1 were given"
+    );
+    assert_eq!(
+        error("qreg q[2]; cx q[1], q[1];").0,
+        "`cx` is applied to qubit 1 twice"
+    );
+    assert_eq!(
+        error("qreg q[1]; qreg q[2];").0,
+        "register `q` is already declared"
+    );
+    assert_eq!(
+        error("qreg a[2]; qreg b[3]; cx a, b;").0,
+        "registers of different sizes are applied together"
+    );
+    assert_eq!(
+        error("qreg q[1]; rx(tan(1)) q[0];").0,
+        "unknown function `tan`"
+    );
+    assert_eq!(error("OPENQASM 2.0;").0, "no qubits are declared");
+}
+
+#[test]
+fn circuit_export() {
+    assert_operator_eq!(
+        compute_tensor("CNOT"),
+        compute_tensor("|0><0| x I + |1><1| x X")
+    );
+
+    // Exported programs prepare the same state when read back
+    for expression in [
+        "(H x I) CNOT |00>",
+        "CNOT (H x I) |00>",
+        "(I x CNOT) (CNOT x I) (H x I x I) |000>",
+        "(Z x Y) (X x H) |1+>",
+        "CNOT (|-> x |1>)",
+        "(I x (H x X)) (CNOT x I) |+10>",
+    ] {
+        let ast = parser::dirac(expression).unwrap();
+        let circuit = qasm(&ast.to_qasm().unwrap()).unwrap();
+
+        assert_state_eq!(circuit.state().compute().unwrap(), ast.compute().unwrap());
+    }
+
+    assert_eq!(
+        parser::dirac("CNOT (H x I) |01>")
+            .unwrap()
+            .to_qasm()
+            .unwrap(),
+        "OPENQASM 3.0;\ninclude \"stdgates.inc\";\nqubit[2] q;\n\
+         x q[1];\nh q[0];\ncx q[0], q[1];\n"
+    );
+    assert_eq!(
+        parser::dirac("(X x CNOT) |-00>")
+            .unwrap()
+            .to_quil()
+            .unwrap(),
+        "H 0\nDAGGER S 0\nX 0\nCNOT 1 2\n"
     );
 
-    assert!((compute_tensor("|1> x |0>") - compute_tensor("|10>")).norm() < EPSILON);
+    let error = |expression| {
+        let error = parser::dirac(expression).unwrap().to_quil().unwrap_err();
+
+        (error.message, error.span)
+    };
+
+    assert_eq!(
+        error("(H x X') |00>"),
+        (
+            "only the gates I, X, Y, Z, H and CNOT can be exported".to_string(),
+            Span::new(5, 7)
+        )
+    );
+    assert_eq!(
+        error("CNOT |000>"),
+        (
+            "a gate on 2 qubit(s) is applied to 3 qubit(s)".to_string(),
+            Span::new(0, 4)
+        )
+    );
+    assert_eq!(
+        error("H (|0> + |1>)").0,
+        "expected gates applied to a basis state, such as `(H x I) CNOT |00>`"
+    );
+}
 
-    assert!((compute_tensor("|0>' - <0|").norm() < EPSILON));
-    assert!((compute_tensor("|0> - <0|'").norm() < EPSILON));
-    assert!((compute_tensor("<0|' - |0>").norm() < EPSILON));
-    assert!((compute_tensor("<0| - |0>'").norm() < EPSILON));
+#[test]
+fn latex() {
+    let latex = |expression| parser::dirac(expression).unwrap().to_latex();
 
-    assert!((compute_complex("||1>|") - c![1.0]).norm() < EPSILON);
+    assert_eq!(
+        latex("1/sqrt(2) (|0> + |1>)"),
+        r"\frac{1}{\sqrt{2}}(\vert 0\rangle + \vert 1\rangle)"
+    );
+    assert_eq!(
+        latex("(|0> - i|1>)/sqrt(2)"),
+        r"\frac{\vert 0\rangle - i \vert 1\rangle}{\sqrt{2}}"
+    );
+    assert_eq!(
+        latex("<0|+> |0><1| x H'"),
+        r"\langle 0\vert +\rangle \vert 0\rangle\langle 1\vert \otimes H^\dagger"
+    );
+    assert_eq!(
+        latex("CNOT (H x I) |00>"),
+        r"\mathrm{CNOT}(H \otimes I) \vert 00\rangle"
+    );
+    assert_eq!(latex("-(X + Y) + 2 * 3.5 X"), r"-(X + Y) + 2 \cdot 3.5 X");
+    assert_eq!(latex("-2^-2^2"), r"-2^{-2^{2}}");
+    assert_eq!(
+        latex("(X Y)^2 - |0>'"),
+        r"(X Y)^{2} - \vert 0\rangle^\dagger"
+    );
+    assert_eq!(
+        latex("[X, Y] + {Z, X} + inv(X) tr(Z) e^(i pi)"),
+        r"[X, Y] + \{Z, X\} + X^{-1} \operatorname{tr}(Z) e^{i \pi}"
+    );
+    assert_eq!(
+        latex("n = 3; sum_{i=0}^{n-2} Z_i Z_{i+1}"),
+        r"n = 3;\ \sum_{i=0}^{n-2} Z_{i} Z_{i+1}"
+    );
+    assert_eq!(
+        latex("ψ = |0> + |1>; ⟨ψ|X|ψ⟩ + ⟨ψ|0⟩"),
+        r"ψ = \vert 0\rangle + \vert 1\rangle;\ \langle ψ\vert X \vert ψ\rangle + \langle ψ\vert 0\rangle"
+    );
+    assert_eq!(
+        latex("mutual_info(|00>, 0) + abs(| |0> |)"),
+        r"\operatorname{mutual\_info}(\vert 00\rangle, 0) + \lvert \lVert \vert 0\rangle \rVert\rvert"
+    );
+
+    // Trees that were not parsed are parenthised where needed
+    let tree = qasm("qreg q[2]; rx(pi/2) q[1];").unwrap().unitary();
+    assert_eq!(
+        tree.to_latex(),
+        r"I \otimes (\cos(\frac{\frac{\pi}{2}}{2}) I - i \sin(\frac{\frac{\pi}{2}}{2}) X)"
+    );
+
+    // Results are matrices, with exact entries when possible
+    assert_eq!(
+        exact("(|0> - i|1>)/sqrt(2)").to_latex(),
+        r"\begin{bmatrix} \frac{\sqrt{2}}{2} \\ -\frac{i\sqrt{2}}{2} \end{bmatrix}"
+    );
+    assert_eq!(
+        exact("exp(i * pi/5) |0><0| + (1 + i) |1><1|").to_latex(),
+        r"\begin{bmatrix} e^{i\pi/5} & 0 \\ 0 & 1 + i \end{bmatrix}"
+    );
+    assert_eq!(
+        compute_tensor("ln(2) Y").to_latex(),
+        format!(
+            r"\begin{{bmatrix}} 0 & -{0}i \\ {0}i & 0 \end{{bmatrix}}",
+            2f64.ln()
+        )
+    );
+    assert_eq!(compute_tensor("1.5 - 2i").to_latex(), "1.5 - 2i");
+}
+
+#[test]
+fn unicode() {
+    for (unicode, ascii) in [
+        (
+            "(|0⟩ ⊗ |1⟩ + |1⟩ ⊗ |0⟩)/√2",
+            "(|0> x |1> + |1> x |0>)/sqrt(2)",
+        ),
+        ("⟨+|0⟩ · |0⟩⟨1|†", "<+|0> * |0><1|'"),
+        ("√(1 + i) X† Y", "sqrt(1 + i) X' Y"),
+        ("exp(i π/4)", "exp(i pi/4)"),
+        ("α = 1; β = 2; Z_α X_β |000⟩", "Z1 X2 |000>"),
+        ("sum_{φ=0}^{1} Z_φ |01⟩", "sum_{i=0}^{1} Z_i |01>"),
+    ] {
+        assert_operator_eq!(compute_tensor(unicode), compute_tensor(ascii));
+    }
+
+    assert_eq!(exact("(|0⟩ + |1⟩)/√2"), exact("(|0> + |1>)/sqrt(2)"));
+}
+
+#[test]
+fn named_states() {
+    for (named, expanded) in [
+        ("ψ = (|0⟩ + |1⟩)/√2; |ψ⟩", "|+>"),
+        ("ψ = (|0⟩ + |1⟩)/√2; ⟨ψ|", "<+|"),
+        ("ψ = (|0⟩ + |1⟩)/√2; ⟨ψ|X|ψ⟩", "1"),
+        ("ψ = (|0⟩ + |1⟩)/√2; |ψ⟩⟨ψ|", "|+><+|"),
+        ("ψ = |0>; φ = |+>; ⟨ψ|φ⟩ + <φ|1>", "<0|+> + <+|1>"),
+        ("ψ = |0>; |ψ⟩' x ⟨ψ|", "<00|"),
+        ("ψ = |00⟩; X0 |ψ⟩", "|10>"),
+        // Bound values are evaluated where they are bound
+        ("ψ = |0>; ψ = |ψ⟩ x |1>; |ψ⟩", "|01>"),
+        ("n = 1; ψ = X_n |00>; sum_{n=0}^{1} ⟨ψ|Z_n|ψ⟩", "0"),
+    ] {
+        assert_operator_eq!(compute_tensor(named), compute_tensor(expanded));
+    }
+
+    assert_eq!(exact("ψ = (|0⟩ + |1⟩)/√2; ⟨ψ|ψ⟩"), exact("1"));
+    assert!(matches!(
+        optimize("ψ = |0>; |ψ⟩'"),
+        Kind::LetState(_, _, body) if matches!(body.kind, Kind::NamedBra(_))
+    ));
+
+    assert_eq!(
+        check_error("ψ = |0>; ⟨ψ|φ⟩"),
+        ("unbound state `φ`".to_string(), Span::new(15, 21))
+    );
+    assert_eq!(
+        check_error("ψ = |0><1|; |ψ⟩"),
+        (
+            "`ψ` must be bound to a ket, got an operator of shape 2x2".to_string(),
+            Span::new(5, 11)
+        )
+    );
+}
+
+#[test]
+fn string_literals() {
+    assert_eq!(unquote(r#""|0⟩ ⊗ |1⟩""#), Some("|0⟩ ⊗ |1⟩".to_string()));
+    assert_eq!(
+        unquote(r#""\u{27e8}0| \"\\ \x41\t""#),
+        Some("⟨0| \"\\ A\t".to_string())
+    );
+    assert_eq!(
+        unquote("\"(|0⟩ \\\n     + |1⟩)/√2\""),
+        Some("(|0⟩ + |1⟩)/√2".to_string())
+    );
+    assert_eq!(
+        unquote(r###"r#"\u{27e8}"0|"#"###),
+        Some(r#"\u{27e8}"0|"#.to_string())
+    );
+    assert_eq!(unquote("1.5"), None);
+    assert_eq!(unquote("b\"01\""), None);
+}
+
+#[test]
+fn code_generation() {
+    let code = |expression| generate(parser::dirac(expression).unwrap(), "");
 
-    assert!(compute_complex("||1>| - |<1||").norm() < EPSILON);
+    assert!(code("|0> + 0.5 |1>").unwrap().contains("(0.5f64, 0f64)"));
+    for expression in ["1/0", "ln(0) |0>", "X / 0"] {
+        let error = code(expression).unwrap_err();
+        assert_eq!(error.message, "the result has infinite or NaN amplitudes");
+        assert_eq!(error.span, Span::new(0, expression.len()));
+    }
 }
@@ -0,0 +1,182 @@
+//! Approximate comparison of tensors, and the assertion macros built on it.
+
+use super::linalg::TOLERANCE;
+use super::{Tensor, C, R};
+use std::fmt::Write;
+
+/// Default absolute tolerance of the assertion macros, as in `numpy.isclose`
+pub const DEFAULT_ATOL: R = 1e-8;
+
+/// Default relative tolerance of the assertion macros, as in `numpy.isclose`
+pub const DEFAULT_RTOL: R = 1e-5;
+
+// Most mismatching entries listed by a diff
+const MAX_DIFF_LINES: usize = 16;
+
+fn close(a: C, b: C, atol: R, rtol: R) -> bool {
+    (a - b).norm() <= atol + rtol * b.norm()
+}
+
+impl Tensor {
+    // Element-wise |a - b| <= atol + rtol |b| with matching shapes
+    pub fn approx_eq(&self, other: &Tensor, atol: R, rtol: R) -> bool {
+        self.shape == other.shape
+            && self
+                .data
+                .iter()
+                .zip(other.data.iter())
+                .all(|(a, b)| close(*a, *b, atol, rtol))
+    }
+
+    // Whether self = e^{iφ} other for some global phase φ. The phase is taken
+    // from the largest entry of other.
+    pub fn eq_up_to_global_phase(&self, other: &Tensor, atol: R, rtol: R) -> bool {
+        if self.shape != other.shape {
+            return false;
+        }
+
+        let largest = other
+            .data
+            .iter()
+            .zip(self.data.iter())
+            .max_by(|(a, _), (b, _)| a.norm().total_cmp(&b.norm()));
+
+        match largest {
+            Some((b, a)) if b.norm() > atol && a.norm() > atol => {
+                let phase = a / b;
+                self.approx_eq(&(other * (phase / phase.norm())), atol, rtol)
+            }
+            _ => self.approx_eq(other, atol, rtol),
+        }
+    }
+
+    // Removes the global phase by making the first entry that is not
+    // negligible against the norm real and positive, so that states differing
+    // only by a global phase have the same canonical form
+    pub fn canonical_phase(&self) -> Tensor {
+        let threshold = TOLERANCE * self.norm();
+
+        match self.data.iter().find(|c| c.norm() > threshold) {
+            Some(c) => self * (c.norm() / c),
+            None => self.clone(),
+        }
+    }
+
+    // Human readable table of the entries that differ between two tensors,
+    // used by assert_state_eq! and assert_operator_eq!. Entries of column
+    // vectors are labelled by their basis ket, others by (row, column).
+    pub fn diff(&self, other: &Tensor, atol: R, rtol: R) -> String {
+        if self.shape != other.shape {
+            return format!("shapes differ: {:?} != {:?}", self.shape, other.shape);
+        }
+
+        let label = |k: usize| match self.shape {
+            (n, 1) if n.is_power_of_two() && n > 1 => {
+                format!("|{:0width$b}>", k, width = n.trailing_zeros() as usize)
+            }
+            (_, 1) => format!("|{}>", k),
+            (_, n) => format!("({}, {})", k / n, k % n),
+        };
+        let mismatches: Vec<usize> = (0..self.data.len())
+            .filter(|&k| !close(self.data[k], other.data[k], atol, rtol))
+            .collect();
+
+        let mut table = format!(
+            "{} of {} entries differ (atol = {:e}, rtol = {:e}):\n{:>12}  {:>24}  {:>24}  {:>10}",
+            mismatches.len(),
+            self.data.len(),
+            atol,
+            rtol,
+            "entry",
+            "left",
+            "right",
+            "|diff|"
+        );
+
+        for &k in mismatches.iter().take(MAX_DIFF_LINES) {
+            let (a, b) = (self.data[k], other.data[k]);
+
+            write!(
+                table,
+                "\n{:>12}  {:>24}  {:>24}  {:>10.3e}",
+                label(k),
+                format!("{:.8}", a),
+                format!("{:.8}", b),
+                (a - b).norm()
+            )
+            .unwrap();
+        }
+
+        if mismatches.len() > MAX_DIFF_LINES {
+            write!(
+                table,
+                "\n... and {} more",
+                mismatches.len() - MAX_DIFF_LINES
+            )
+            .unwrap();
+        }
+
+        table
+    }
+}
+
+/// Asserts that two state vectors are equal within tolerances, printing the
+/// amplitudes that differ on failure. Tolerances default to
+/// [`DEFAULT_ATOL`] and [`DEFAULT_RTOL`].
+///
+/// ```
+/// use tensor::{assert_state_eq, Tensor};
+/// use num::complex::Complex64;
+///
+/// let zero = Tensor::new(vec![Complex64::new(1.0, 0.0), Complex64::new(0.0, 0.0)], (2, 1));
+///
+/// assert_state_eq!(zero, zero.unit());
+/// assert_state_eq!(zero, &zero * 1.001, 0.0, 1e-2);
+/// ```
+#[macro_export]
+macro_rules! assert_state_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::assert_state_eq!($left, $right, $crate::DEFAULT_ATOL, $crate::DEFAULT_RTOL)
+    };
+    ($left:expr, $right:expr, $atol:expr, $rtol:expr $(,)?) => {{
+        let (left, right): (&$crate::Tensor, &$crate::Tensor) = (&$left, &$right);
+
+        assert!(
+            left.shape.1 == 1 && right.shape.1 == 1,
+            "assert_state_eq! expects column vectors, got shapes {:?} and {:?}",
+            left.shape,
+            right.shape
+        );
+
+        if !left.approx_eq(right, $atol, $rtol) {
+            panic!(
+                "assertion `left ≈ right` failed for states\n  left: {}\n right: {}\n{}",
+                stringify!($left),
+                stringify!($right),
+                left.diff(right, $atol, $rtol)
+            );
+        }
+    }};
+}
+
+/// Asserts that two operators (or any tensors of equal shape) are equal
+/// within tolerances, printing the entries that differ on failure.
+/// Tolerances default to [`DEFAULT_ATOL`] and [`DEFAULT_RTOL`].
+#[macro_export]
+macro_rules! assert_operator_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::assert_operator_eq!($left, $right, $crate::DEFAULT_ATOL, $crate::DEFAULT_RTOL)
+    };
+    ($left:expr, $right:expr, $atol:expr, $rtol:expr $(,)?) => {{
+        let (left, right): (&$crate::Tensor, &$crate::Tensor) = (&$left, &$right);
+
+        if !left.approx_eq(right, $atol, $rtol) {
+            panic!(
+                "assertion `left ≈ right` failed for operators\n  left: {}\n right: {}\n{}",
+                stringify!($left),
+                stringify!($right),
+                left.diff(right, $atol, $rtol)
+            );
+        }
+    }};
+}
@@ -0,0 +1,261 @@
+//! Entanglement structure and information-theoretic measures of states.
+
+use super::linalg::TOLERANCE;
+use super::{Tensor, C, R};
+
+/// Schmidt decomposition |ψ> = Σ_k s_k |a_k> ⊗ |b_k> of a bipartite pure
+/// state, with the coefficients in descending order.
+#[derive(Debug, Clone)]
+pub struct Schmidt {
+    pub coefficients: Vec<R>,
+    pub a: Vec<Tensor>,
+    pub b: Vec<Tensor>,
+}
+
+impl Schmidt {
+    // Number of non-zero Schmidt coefficients, 1 for product states
+    pub fn rank(&self) -> usize {
+        let largest = self.coefficients.first().cloned().unwrap_or(0.0);
+
+        self.coefficients
+            .iter()
+            .filter(|&&s| s > TOLERANCE * largest.max(1.0))
+            .count()
+    }
+}
+
+impl Tensor {
+    // Schmidt decomposition of a state on a dims_a x dims_b system. Thanks to
+    // the row-major layout, the state vector already is the dims_a x dims_b
+    // coefficient matrix M with ψ = Σ M[i, j] |i> ⊗ |j>, so it only needs a
+    // new shape before the SVD M = U diag(s) V†. The local vectors are then
+    // the columns of U and the conjugated columns of V.
+    pub fn schmidt(&self, dims_a: usize, dims_b: usize) -> Schmidt {
+        assert!(
+            self.data.len() == dims_a * dims_b,
+            "a state of {} amplitudes cannot be split into {} x {}",
+            self.data.len(),
+            dims_a,
+            dims_b
+        );
+
+        let (u, s, v) = Tensor::new(self.data.clone(), (dims_a, dims_b)).svd();
+        let k = s.shape.0;
+
+        Schmidt {
+            coefficients: s.data.iter().map(|c| c.re).collect(),
+            a: (0..k).map(|j| u.column(j)).collect(),
+            b: (0..k).map(|j| v.column(j).map(|c| c.conj())).collect(),
+        }
+    }
+}
+
+// Splits a flat index into per-subsystem indices, with the first subsystem
+// being the most significant, as in a Kronecker product
+fn digits(mut index: usize, dims: &[usize]) -> Vec<usize> {
+    let mut digits = vec![0; dims.len()];
+
+    for (k, &d) in dims.iter().enumerate().rev() {
+        digits[k] = index % d;
+        index /= d;
+    }
+
+    digits
+}
+
+// Inverse of digits, over a subset of the subsystems
+fn flatten(digits: &[usize], dims: &[usize], subsystems: &[usize]) -> usize {
+    subsystems
+        .iter()
+        .fold(0, |index, &k| index * dims[k] + digits[k])
+}
+
+// Checks that the subsystem dimensions multiply to the dimension of the
+// state, and that the subsystems in sys exist
+fn check_subsystems(rho: &Tensor, dims: &[usize], sys: &[usize]) {
+    assert!(
+        rho.shape.0 == dims.iter().product::<usize>(),
+        "subsystem dimensions {:?} do not match a state of dimension {}",
+        dims,
+        rho.shape.0
+    );
+    for &k in sys {
+        assert!(
+            k < dims.len(),
+            "subsystem {} is out of range for {} subsystems",
+            k,
+            dims.len()
+        );
+    }
+}
+
+// Matrix elements (row, column, value) of an operator on the given subsystems
+fn entries<'a>(
+    rho: &'a Tensor,
+    dims: &'a [usize],
+) -> impl Iterator<Item = (Vec<usize>, Vec<usize>, C)> + 'a {
+    (0..rho.shape.0).flat_map(move |i| {
+        (0..rho.shape.1).map(move |j| (digits(i, dims), digits(j, dims), rho[(i, j)]))
+    })
+}
+
+// Sum of the entropy contributions f(λ) of the non-zero eigenvalues
+fn spectrum_sum<F: Fn(R) -> R>(rho: &Tensor, f: F) -> R {
+    let (eigenvalues, _) = rho.eigh();
+
+    eigenvalues
+        .data
+        .iter()
+        .map(|c| c.re)
+        .filter(|&lambda| lambda > TOLERANCE)
+        .map(f)
+        .sum()
+}
+
+// Square root of a positive semidefinite matrix. Rounding can leave tiny
+// negative eigenvalues, which are clamped so the root stays Hermitian.
+fn psd_sqrt(rho: &Tensor) -> Tensor {
+    rho.spectral_map(|lambda| C::new(lambda.max(0.0).sqrt(), 0.0))
+}
+
+impl Tensor {
+    // Density operator of a state: |ψ><ψ| for kets, the operator itself
+    // otherwise
+    pub fn density(&self) -> Tensor {
+        match self.shape {
+            (_, 1) => self.proj(),
+            (m, n) if m == n => self.clone(),
+            shape => panic!("a tensor of shape {:?} is not a state", shape),
+        }
+    }
+
+    // Partial trace over the subsystems in sys, for a state on subsystems of
+    // the given dimensions
+    pub fn ptrace(&self, dims: &[usize], sys: &[usize]) -> Tensor {
+        let rho = self.density();
+        check_subsystems(&rho, dims, sys);
+
+        let kept: Vec<usize> = (0..dims.len()).filter(|k| !sys.contains(k)).collect();
+        let n = kept.iter().map(|&k| dims[k]).product();
+        let mut reduced = Tensor::new(vec![C::new(0.0, 0.0); n * n], (n, n));
+
+        for (row, column, value) in entries(&rho, dims) {
+            if sys.iter().all(|&k| row[k] == column[k]) {
+                reduced[(flatten(&row, dims, &kept), flatten(&column, dims, &kept))] += value;
+            }
+        }
+
+        reduced
+    }
+
+    // Partial transpose over the subsystems in sys: the transpose is taken on
+    // those subsystems' indices only, leaving the others untouched. Kets are
+    // turned into density operators first.
+    pub fn partial_transpose(&self, dims: &[usize], sys: &[usize]) -> Tensor {
+        let rho = self.density();
+        check_subsystems(&rho, dims, sys);
+
+        let all: Vec<usize> = (0..dims.len()).collect();
+        let mut transposed = rho.clone();
+
+        for (mut row, mut column, value) in entries(&rho, dims) {
+            for &k in sys {
+                std::mem::swap(&mut row[k], &mut column[k]);
+            }
+
+            transposed[(flatten(&row, dims, &all), flatten(&column, dims, &all))] = value;
+        }
+
+        transposed
+    }
+
+    // Peres–Horodecki criterion: whether the partial transpose over sys is
+    // positive semidefinite. Entangled states across the cut can have a
+    // positive partial transpose, except on 2 x 2 and 2 x 3 systems where
+    // PPT states are exactly the separable ones.
+    pub fn is_ppt(&self, dims: &[usize], sys: &[usize]) -> bool {
+        self.partial_transpose(dims, sys).is_positive_semidefinite()
+    }
+
+    // Von Neumann entropy -tr(ρ log₂ ρ), in bits
+    pub fn entropy(&self) -> R {
+        spectrum_sum(&self.density(), |lambda| -lambda * lambda.log2())
+    }
+
+    // Rényi entropy log₂(tr ρ^α) / (1 - α), in bits. The α = 1 limit is the
+    // von Neumann entropy.
+    pub fn renyi_entropy(&self, alpha: R) -> R {
+        if (alpha - 1.0).abs() <= TOLERANCE {
+            return self.entropy();
+        }
+
+        spectrum_sum(&self.density(), |lambda| lambda.powf(alpha)).log2() / (1.0 - alpha)
+    }
+
+    // Wootters concurrence of a two qubit state
+    pub fn concurrence(&self) -> R {
+        let rho = self.density();
+        assert!(
+            rho.shape == (4, 4),
+            "concurrence is only defined for two qubits"
+        );
+
+        // Spin flipped state (Y ⊗ Y) ρ* (Y ⊗ Y)
+        let y = Tensor::new(
+            vec![
+                C::new(0.0, 0.0),
+                C::new(0.0, -1.0),
+                C::new(0.0, 1.0),
+                C::new(0.0, 0.0),
+            ],
+            (2, 2),
+        );
+        let yy = y.prod(&y);
+        let flipped = &(&yy * &rho.map(|c| c.conj())) * &yy;
+
+        // The square roots of the eigenvalues of √ρ ρ̃ √ρ, in descending order
+        let root = psd_sqrt(&rho);
+        let (eigenvalues, _) = (&(&root * &flipped) * &root).eigh();
+        let lambda: Vec<R> = eigenvalues
+            .data
+            .iter()
+            .rev()
+            .map(|c| c.re.max(0.0).sqrt())
+            .collect();
+
+        (lambda[0] - lambda[1] - lambda[2] - lambda[3]).max(0.0)
+    }
+
+    // Negativity (‖ρ^T‖₁ - 1) / 2 with respect to the partial transpose over
+    // the subsystems in sys, the sum of the magnitudes of the negative
+    // eigenvalues of ρ^T
+    pub fn negativity(&self, dims: &[usize], sys: &[usize]) -> R {
+        let (eigenvalues, _) = self.partial_transpose(dims, sys).eigh();
+
+        eigenvalues.data.iter().map(|c| (-c.re).max(0.0)).sum()
+    }
+
+    // Quantum mutual information S(A) + S(B) - S(AB) between the subsystems
+    // in sys (A) and the rest (B)
+    pub fn mutual_information(&self, dims: &[usize], sys: &[usize]) -> R {
+        let rest: Vec<usize> = (0..dims.len()).filter(|k| !sys.contains(k)).collect();
+
+        self.ptrace(dims, &rest).entropy() + self.ptrace(dims, sys).entropy() - self.entropy()
+    }
+
+    // Uhlmann fidelity (tr √(√ρ σ √ρ))², which is |<ψ|φ>|² for pure states
+    pub fn fidelity(&self, other: &Tensor) -> R {
+        let root = psd_sqrt(&self.density());
+        let product = &(&root * &other.density()) * &root;
+        let trace = spectrum_sum(&product, |lambda| lambda.sqrt());
+
+        trace * trace
+    }
+
+    // Trace distance ½ ‖ρ - σ‖₁
+    pub fn trace_distance(&self, other: &Tensor) -> R {
+        let (eigenvalues, _) = (self.density() - other.density()).eigh();
+
+        eigenvalues.data.iter().map(|c| c.re.abs()).sum::<R>() / 2.0
+    }
+}
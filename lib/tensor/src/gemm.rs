@@ -0,0 +1,72 @@
+//! Matrix products: a cache-blocked kernel for operators, a row by row one for
+//! operators applied to kets, and with the `rayon` feature both spread over
+//! threads for large matrices.
+
+use super::{Tensor, C};
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+// Side of the square blocks the operands are cut into, so that a block of
+// each operand stays in cache while it is being used
+const BLOCK: usize = 64;
+
+// Smallest number of multiplications worth spreading over threads
+#[cfg(feature = "rayon")]
+const PARALLEL_THRESHOLD: usize = 1 << 18;
+
+// Product of an m x n and an n x p matrix. Every entry is accumulated in the
+// same order as the textbook triple loop, from k = 0 up, so results do not
+// depend on the blocking nor on the number of threads.
+pub(crate) fn matmul(a: &Tensor, b: &Tensor) -> Tensor {
+    let shape = (a.shape.0, b.shape.1);
+    let mut data = vec![C::new(0f64, 0f64); shape.0 * shape.1];
+    let chunk = (BLOCK * shape.1).max(1);
+
+    #[cfg(feature = "rayon")]
+    if a.shape.0 * a.shape.1 * b.shape.1 >= PARALLEL_THRESHOLD {
+        data.par_chunks_mut(chunk)
+            .enumerate()
+            .for_each(|(k, rows)| multiply_rows(a, b, k * BLOCK, rows));
+
+        return Tensor::new(data, shape);
+    }
+
+    data.chunks_mut(chunk)
+        .enumerate()
+        .for_each(|(k, rows)| multiply_rows(a, b, k * BLOCK, rows));
+
+    Tensor::new(data, shape)
+}
+
+// Computes the rows of a b starting at the given one into out, which holds at
+// most BLOCK rows and is zeroed
+fn multiply_rows(a: &Tensor, b: &Tensor, first: usize, out: &mut [C]) {
+    let (n, p) = (a.shape.1, b.shape.1);
+    let row = |i: usize| &a.data[(first + i) * n..(first + i + 1) * n];
+
+    // Operator on a ket: one dot product per row
+    if p == 1 {
+        for (i, entry) in out.iter_mut().enumerate() {
+            *entry = row(i).iter().zip(b.data.iter()).map(|(x, y)| x * y).sum();
+        }
+
+        return;
+    }
+
+    for k0 in (0..n).step_by(BLOCK) {
+        let k1 = (k0 + BLOCK).min(n);
+
+        for j0 in (0..p).step_by(BLOCK) {
+            let j1 = (j0 + BLOCK).min(p);
+
+            for (i, out_row) in out.chunks_exact_mut(p).enumerate() {
+                for (x, b_row) in row(i)[k0..k1].iter().zip(b.data[k0 * p..].chunks(p)) {
+                    for (entry, y) in out_row[j0..j1].iter_mut().zip(&b_row[j0..j1]) {
+                        *entry += x * y;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Kronecker products of operators kept as their factors, and applied to
+//! vectors one factor at a time.
+
+use super::{KroneckerProduct, Tensor, C};
+use std::ops::Mul;
+
+/// The Kronecker product A_0 ⊗ A_1 ⊗ ... of operators, stored as its factors.
+/// Applying it to a vector costs about one multiplication per entry of the
+/// vector and row of a factor, instead of one per entry of the full matrix,
+/// which is only built by `to_tensor`.
+#[derive(Debug, Clone)]
+pub struct KronOp {
+    pub factors: Vec<Tensor>,
+}
+
+impl KronOp {
+    pub fn new(factors: Vec<Tensor>) -> KronOp {
+        assert!(
+            !factors.is_empty(),
+            "a Kronecker product needs at least one factor"
+        );
+
+        KronOp { factors }
+    }
+
+    pub fn shape(&self) -> (usize, usize) {
+        self.factors.iter().fold((1, 1), |(m, n), factor| {
+            (m * factor.shape.0, n * factor.shape.1)
+        })
+    }
+
+    // A ⊗ B, keeping the factors of both
+    pub fn kron(mut self, rhs: KronOp) -> KronOp {
+        self.factors.extend(rhs.factors);
+        self
+    }
+
+    pub fn dag(&self) -> KronOp {
+        KronOp::new(self.factors.iter().map(|factor| factor.dag()).collect())
+    }
+
+    // Scales the whole product through its first factor
+    pub fn scale(mut self, c: C) -> KronOp {
+        self.factors[0] *= c;
+        self
+    }
+
+    // (A_0 ⊗ A_1 ⊗ ...)(B_0 ⊗ B_1 ⊗ ...) = A_0 B_0 ⊗ A_1 B_1 ⊗ ..., when the
+    // factors pair up
+    pub fn compose(&self, rhs: &KronOp) -> Option<KronOp> {
+        let paired = self.factors.len() == rhs.factors.len()
+            && self
+                .factors
+                .iter()
+                .zip(rhs.factors.iter())
+                .all(|(a, b)| a.shape.1 == b.shape.0);
+
+        paired.then(|| {
+            KronOp::new(
+                self.factors
+                    .iter()
+                    .zip(rhs.factors.iter())
+                    .map(|(a, b)| a * b)
+                    .collect(),
+            )
+        })
+    }
+
+    pub fn to_tensor(&self) -> Tensor {
+        self.factors.prod()
+    }
+}
+
+// Product with a matrix of k columns, such as a ket. Its rows are indexed by
+// (j_0, j_1, ...) and each factor A_t replaces j_t by i_t in turn: seen as an
+// array of shape (l, q, r), with l covering the indices already replaced and r
+// the remaining ones along with the columns, entry (a, i, b) of the next array
+// is the sum over j of A_t[(i, j)] x[(a, j, b)]. Zero entries of the factors,
+// common in gates, are skipped.
+impl Mul<&Tensor> for &KronOp {
+    type Output = Tensor;
+
+    fn mul(self, rhs: &Tensor) -> Tensor {
+        let (m, n) = self.shape();
+        assert!(n == rhs.shape.0);
+
+        let zero = C::new(0.0, 0.0);
+        let columns = rhs.shape.1;
+        let (mut x, mut y) = (rhs.data.clone(), Vec::new());
+        let (mut l, mut r) = (1, n * columns);
+
+        for factor in self.factors.iter() {
+            let (p, q) = factor.shape;
+            r /= q;
+
+            y.clear();
+            y.resize(l * p * r, zero);
+
+            for (input, output) in x.chunks_exact(q * r).zip(y.chunks_exact_mut(p * r)) {
+                for (i, row) in output.chunks_exact_mut(r).enumerate() {
+                    for (j, slice) in input.chunks_exact(r).enumerate() {
+                        let a = factor[(i, j)];
+
+                        if a != zero {
+                            row.iter_mut().zip(slice).for_each(|(y, x)| *y += a * x);
+                        }
+                    }
+                }
+            }
+
+            std::mem::swap(&mut x, &mut y);
+            l *= p;
+        }
+
+        Tensor::new(x, (m, columns))
+    }
+}
+
+// Product of a matrix, such as a bra, with the operator: x A = (A† x†)†
+impl Mul<&KronOp> for &Tensor {
+    type Output = Tensor;
+
+    fn mul(self, rhs: &KronOp) -> Tensor {
+        (&rhs.dag() * &self.dag()).dag()
+    }
+}
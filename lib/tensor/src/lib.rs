@@ -2,10 +2,34 @@
 
 use num::complex::Complex64;
 use std::{
+    borrow::Borrow,
     fmt::{self, Display},
-    ops::{Add, BitOr, Div, Index, Mul, Sub},
+    ops::{
+        Add, AddAssign, BitOr, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
+    },
 };
 
+mod compare;
+mod entanglement;
+mod gemm;
+mod kron;
+mod linalg;
+mod matfun;
+#[cfg(feature = "npy")]
+mod npy;
+mod pauli;
+#[cfg(feature = "serde")]
+mod serialize;
+
+pub use compare::{DEFAULT_ATOL, DEFAULT_RTOL};
+pub use entanglement::Schmidt;
+pub use kron::KronOp;
+pub use linalg::LinalgError;
+pub use pauli::{Pauli, PauliString};
+
+#[cfg(test)]
+mod tests;
+
 type R = f64;
 type C = Complex64;
 type Data = Vec<C>;
@@ -105,61 +129,200 @@ impl Tensor {
         self * &self.dag()
     }
 
-    // Kronecker product
-    pub fn prod(&self, rhs: &Tensor) -> Tensor {
-        let shape = (self.shape.0 * rhs.shape.0, self.shape.1 * rhs.shape.1);
-        let mut data = vec![C::new(0f64, 0f64); shape.0 * shape.1];
-
-        // Walk the first matrix
-        for i in 0..self.shape.1 {
-            for j in 0..self.shape.0 {
-                // For each element, walk the second matrix
-                for k in 0..rhs.shape.1 {
-                    for l in 0..rhs.shape.0 {
-                        let x = i * rhs.shape.1 + k;
-                        let y = j * rhs.shape.0 + l;
-
-                        data[x + y * shape.1] = self[(j, i)] * rhs[(l, k)];
-                    }
-                }
-            }
+    // Commutator [A, B] = AB - BA
+    pub fn commutator(&self, rhs: &Tensor) -> Tensor {
+        self * rhs - rhs * self
+    }
+
+    // Anticommutator {A, B} = AB + BA
+    pub fn anticommutator(&self, rhs: &Tensor) -> Tensor {
+        self * rhs + rhs * self
+    }
+
+    // Applies a function to every element
+    pub fn map<F: Fn(C) -> C>(&self, f: F) -> Tensor {
+        Tensor::new(self.data.iter().map(|c| f(*c)).collect(), self.shape)
+    }
+
+    // Matrix power, only defined for square matrices. Negative powers are
+    // powers of the inverse.
+    pub fn powi(&self, n: i32) -> Tensor {
+        if n < 0 {
+            return match self.inv() {
+                Ok(inverse) => inverse.powu(n.unsigned_abs()),
+                Err(e) => panic!("cannot raise to a negative power: {}", e),
+            };
         }
 
-        Tensor::new(data, shape)
+        self.powu(n.unsigned_abs())
     }
 
-    pub fn expand(&self, n: usize, i: usize) -> Tensor {
-        let eye = Tensor::eye(2);
-        let mut product = if i == 0 { self.clone() } else { eye.clone() };
+    // Non-negative matrix power by repeated squaring
+    pub fn powu(&self, mut n: u32) -> Tensor {
+        assert!(self.shape.0 == self.shape.1, "powu on non-square tensor");
+
+        let mut result = Tensor::eye(self.shape.0);
+        let mut base = self.clone();
 
-        for k in 1..n {
-            product = if k == i {
-                product.prod(self)
-            } else {
-                product.prod(&eye)
+        while n > 0 {
+            if n & 1 == 1 {
+                result = &result * &base;
             }
+
+            base = &base * &base;
+            n >>= 1;
         }
 
-        product
+        result
+    }
+
+    // Kronecker product
+    pub fn prod(&self, rhs: &Tensor) -> Tensor {
+        [self, rhs].prod()
+    }
+
+    pub fn expand(&self, n: usize, i: usize) -> Tensor {
+        Tensor::expand_sites(n, &[(i, self.clone())])
+    }
+
+    // Kronecker product of single qubit operators placed on the given qubits
+    // of an n qubit register, with identities everywhere else
+    pub fn expand_sites(n: usize, sites: &[(usize, Tensor)]) -> Tensor {
+        let eye = Tensor::eye(2);
+        let factor = |k: usize| match sites.iter().find(|(i, _)| *i == k) {
+            Some((_, tensor)) => tensor,
+            None => &eye,
+        };
+
+        (0..n).map(factor).collect::<Vec<&Tensor>>().prod()
     }
 }
 
+// Element-wise operations between tensors of the same shape, which write into
+// the buffer of an owned operand rather than allocating a new one
 macro_rules! tensor_elementwise_op {
-    ( $trait:ident, $op:ident ) => {
+    ( $trait:ident, $op:ident, $assign_trait:ident, $assign_op:ident ) => {
+        impl $assign_trait<&Tensor> for Tensor {
+            fn $assign_op(&mut self, rhs: &Tensor) {
+                assert!(self.shape == rhs.shape);
+
+                self.data
+                    .iter_mut()
+                    .zip(rhs.data.iter())
+                    .for_each(|(c1, c2)| c1.$assign_op(c2));
+            }
+        }
+
+        impl $assign_trait for Tensor {
+            fn $assign_op(&mut self, rhs: Tensor) {
+                self.$assign_op(&rhs);
+            }
+        }
+
+        impl $trait<&Tensor> for Tensor {
+            type Output = Tensor;
+
+            fn $op(mut self, rhs: &Tensor) -> Tensor {
+                self.$assign_op(rhs);
+                self
+            }
+        }
+
         impl $trait for Tensor {
             type Output = Tensor;
 
             fn $op(self, rhs: Tensor) -> Tensor {
+                self.$op(&rhs)
+            }
+        }
+
+        impl $trait<Tensor> for &Tensor {
+            type Output = Tensor;
+
+            fn $op(self, mut rhs: Tensor) -> Tensor {
                 assert!(self.shape == rhs.shape);
 
-                Tensor::new(
-                    self.data
-                        .iter()
-                        .zip(rhs.data.iter())
-                        .map(|(c1, c2)| c1.$op(c2))
-                        .collect(),
-                    self.shape,
-                )
+                rhs.data
+                    .iter_mut()
+                    .zip(self.data.iter())
+                    .for_each(|(c2, c1)| *c2 = c1.$op(*c2));
+                rhs
+            }
+        }
+
+        impl $trait for &Tensor {
+            type Output = Tensor;
+
+            fn $op(self, rhs: &Tensor) -> Tensor {
+                self.clone().$op(rhs)
+            }
+        }
+    };
+}
+
+// Multiplication and division by a scalar on either side, in place for owned
+// tensors
+macro_rules! tensor_scalar_op {
+    ( $scalar:ty ) => {
+        impl MulAssign<$scalar> for Tensor {
+            fn mul_assign(&mut self, rhs: $scalar) {
+                self.data.iter_mut().for_each(|c| *c *= rhs);
+            }
+        }
+
+        impl DivAssign<$scalar> for Tensor {
+            fn div_assign(&mut self, rhs: $scalar) {
+                self.data.iter_mut().for_each(|c| *c /= rhs);
+            }
+        }
+
+        impl Mul<$scalar> for Tensor {
+            type Output = Tensor;
+
+            fn mul(mut self, rhs: $scalar) -> Tensor {
+                self *= rhs;
+                self
+            }
+        }
+
+        impl Mul<$scalar> for &Tensor {
+            type Output = Tensor;
+
+            fn mul(self, rhs: $scalar) -> Tensor {
+                self.map(|c| c * rhs)
+            }
+        }
+
+        impl Div<$scalar> for Tensor {
+            type Output = Tensor;
+
+            fn div(mut self, rhs: $scalar) -> Tensor {
+                self /= rhs;
+                self
+            }
+        }
+
+        impl Div<$scalar> for &Tensor {
+            type Output = Tensor;
+
+            fn div(self, rhs: $scalar) -> Tensor {
+                self.map(|c| c / rhs)
+            }
+        }
+
+        impl Mul<Tensor> for $scalar {
+            type Output = Tensor;
+
+            fn mul(self, rhs: Tensor) -> Tensor {
+                rhs * self
+            }
+        }
+
+        impl Mul<&Tensor> for $scalar {
+            type Output = Tensor;
+
+            fn mul(self, rhs: &Tensor) -> Tensor {
+                rhs * self
             }
         }
     };
@@ -173,30 +336,31 @@ impl Index<(usize, usize)> for Tensor {
     }
 }
 
-tensor_elementwise_op!(Add, add);
-tensor_elementwise_op!(Sub, sub);
-
-impl Div<f64> for &Tensor {
-    type Output = Tensor;
-
-    fn div(self, rhs: f64) -> Tensor {
-        Tensor::new(self.data.iter().map(|c| c / rhs).collect(), self.shape)
+impl IndexMut<(usize, usize)> for Tensor {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        &mut self.data[index.1 + index.0 * self.shape.1]
     }
 }
 
-impl Mul<f64> for &Tensor {
+tensor_elementwise_op!(Add, add, AddAssign, add_assign);
+tensor_elementwise_op!(Sub, sub, SubAssign, sub_assign);
+tensor_scalar_op!(R);
+tensor_scalar_op!(C);
+
+impl Neg for Tensor {
     type Output = Tensor;
 
-    fn mul(self, rhs: f64) -> Tensor {
-        Tensor::new(self.data.iter().map(|c| c * rhs).collect(), self.shape)
+    fn neg(mut self) -> Tensor {
+        self.data.iter_mut().for_each(|c| *c = -*c);
+        self
     }
 }
 
-impl Mul<C> for &Tensor {
+impl Neg for &Tensor {
     type Output = Tensor;
 
-    fn mul(self, rhs: C) -> Tensor {
-        Tensor::new(self.data.iter().map(|c| c * rhs).collect(), self.shape)
+    fn neg(self) -> Tensor {
+        self.map(|c| -c)
     }
 }
 
@@ -228,38 +392,84 @@ impl Mul<&Tensor> for &Tensor {
             return self * rhs.item().unwrap();
         }
 
-        let shape = (self.shape.0, rhs.shape.1);
-        let mut data = Vec::<C>::new();
-        let n = self.shape.1;
+        gemm::matmul(self, rhs)
+    }
+}
 
-        for i in 0..shape.0 {
-            for j in 0..shape.1 {
-                data.push((0..n).map(|k| self[(i, k)] * rhs[(k, j)]).sum());
-            }
+// Products with a scalar tensor reuse the buffer of the other operand
+impl Mul for Tensor {
+    type Output = Tensor;
+
+    fn mul(self, rhs: Tensor) -> Tensor {
+        match (self.item(), rhs.item()) {
+            (Some(c), _) => rhs * c,
+            (None, Some(c)) => self * c,
+            (None, None) => &self * &rhs,
         }
+    }
+}
+
+impl Mul<&Tensor> for Tensor {
+    type Output = Tensor;
 
-        Tensor::new(data, shape)
+    fn mul(self, rhs: &Tensor) -> Tensor {
+        match rhs.item() {
+            Some(c) => self * c,
+            None => &self * rhs,
+        }
     }
 }
 
-impl Mul for Tensor {
+impl Mul<Tensor> for &Tensor {
     type Output = Tensor;
 
     fn mul(self, rhs: Tensor) -> Tensor {
-        &self * &rhs
+        match self.item() {
+            Some(c) => rhs * c,
+            None => self * &rhs,
+        }
+    }
+}
+
+impl MulAssign<&Tensor> for Tensor {
+    fn mul_assign(&mut self, rhs: &Tensor) {
+        match rhs.item() {
+            Some(c) => *self *= c,
+            None => *self = &*self * rhs,
+        }
+    }
+}
+
+impl MulAssign for Tensor {
+    fn mul_assign(&mut self, rhs: Tensor) {
+        *self *= &rhs;
     }
 }
 
+// Division by a scalar, or right division A B⁻¹ by an invertible matrix
 impl Div<&Tensor> for &Tensor {
     type Output = Tensor;
 
+    #[allow(clippy::suspicious_arithmetic_impl)]
     fn div(self, rhs: &Tensor) -> Tensor {
-        assert!(rhs.shape == (1, 1));
+        match rhs.item() {
+            Some(c) => self / c,
+            None => match rhs.inv() {
+                Ok(inverse) => self * &inverse,
+                Err(e) => panic!("cannot divide by a matrix: {}", e),
+            },
+        }
+    }
+}
 
-        Tensor::new(
-            self.data.iter().map(|c| c / rhs.item().unwrap()).collect(),
-            self.shape,
-        )
+impl Div<&Tensor> for Tensor {
+    type Output = Tensor;
+
+    fn div(self, rhs: &Tensor) -> Tensor {
+        match rhs.item() {
+            Some(c) => self / c,
+            None => &self / rhs,
+        }
     }
 }
 
@@ -267,7 +477,30 @@ impl Div for Tensor {
     type Output = Tensor;
 
     fn div(self, rhs: Tensor) -> Tensor {
-        &self / &rhs
+        self / &rhs
+    }
+}
+
+impl Div<Tensor> for &Tensor {
+    type Output = Tensor;
+
+    fn div(self, rhs: Tensor) -> Tensor {
+        self / &rhs
+    }
+}
+
+impl DivAssign<&Tensor> for Tensor {
+    fn div_assign(&mut self, rhs: &Tensor) {
+        match rhs.item() {
+            Some(c) => *self /= c,
+            None => *self = &*self / rhs,
+        }
+    }
+}
+
+impl DivAssign for Tensor {
+    fn div_assign(&mut self, rhs: Tensor) {
+        *self /= &rhs;
     }
 }
 
@@ -294,18 +527,94 @@ impl AsTensor for char {
 }
 
 pub trait KroneckerProduct {
-    fn prod(&self) -> Tensor;
+    fn prod(&self) -> Tensor {
+        let mut product = Tensor::new(Vec::new(), (0, 0));
+        self.prod_into(&mut product);
+
+        product
+    }
+
+    // Writes the product into out, reusing its buffer when it is large enough
+    fn prod_into(&self, out: &mut Tensor);
 }
 
-impl KroneckerProduct for Vec<Tensor> {
-    fn prod(&self) -> Tensor {
-        match self
-            .iter()
-            .cloned()
-            .reduce(|product, tensor| product.prod(&tensor))
-        {
-            Some(tensor) => tensor,
-            None => panic!("Should always be called on a nonempty vector"),
+// The n-fold product is built in place in the output buffer, without
+// intermediate tensors, by prepending the factors from the last to the first.
+// With the product P of the later factors (p0 x p1) at the start of the
+// buffer, A ⊗ P is made of one block of p0 rows per row i of A, holding the
+// Kronecker product of that row with P. The blocks for i > 0 lie past P and
+// are written directly, and the one for i = 0, which overlaps P, is written
+// from its last row back so that every row of P is read before it is
+// overwritten. All writes are sequential, and as all but the last prepended
+// factor only touch a fraction of the buffer, the product costs about one
+// multiplication per entry of the result.
+impl<T: Borrow<Tensor>> KroneckerProduct for [T] {
+    fn prod_into(&self, out: &mut Tensor) {
+        assert!(
+            !self.is_empty(),
+            "Should always be called on a nonempty vector"
+        );
+
+        let shape = self.iter().fold((1, 1), |(m, n), tensor| {
+            let shape = tensor.borrow().shape;
+
+            (m * shape.0, n * shape.1)
+        });
+
+        out.shape = shape;
+        out.data.clear();
+        out.data.resize(shape.0 * shape.1, C::new(0f64, 0f64));
+        out.data[0] = C::new(1f64, 0f64);
+
+        let (mut p0, mut p1) = (1, 1);
+
+        for tensor in self.iter().rev() {
+            let tensor = tensor.borrow();
+            let (m, n) = tensor.shape;
+            let block = p0 * n * p1;
+            let row = |i: usize| &tensor.data[i * n..(i + 1) * n];
+
+            let (first, rest) = out.data[..m * block].split_at_mut(block);
+
+            for (i, rows) in rest.chunks_exact_mut(block).enumerate() {
+                expand(rows, &first[..p0 * p1], row(i + 1), p1);
+            }
+
+            let a = row(0);
+
+            match n {
+                // The first block is P itself, scaled
+                1 => first.iter_mut().for_each(|x| *x *= a[0]),
+                _ => {
+                    for r in (1..p0).rev() {
+                        let (head, tail) = first.split_at_mut(r * n * p1);
+                        expand(&mut tail[..n * p1], &head[r * p1..(r + 1) * p1], a, p1);
+                    }
+
+                    // The first row of the block starts with the first row
+                    // of P
+                    for j in (0..n).rev() {
+                        for c in (0..p1).rev() {
+                            first[j * p1 + c] = a[j] * first[c];
+                        }
+                    }
+                }
+            }
+
+            p0 *= m;
+            p1 *= n;
+        }
+    }
+}
+
+// Writes the Kronecker product of the row a of a factor with the rows of p,
+// which have p1 entries each, into out
+fn expand(out: &mut [C], p: &[C], a: &[C], p1: usize) {
+    for (rows, source) in out.chunks_exact_mut(a.len() * p1).zip(p.chunks_exact(p1)) {
+        for (entries, x) in rows.chunks_exact_mut(p1).zip(a) {
+            for (entry, y) in entries.iter_mut().zip(source) {
+                *entry = x * y;
+            }
         }
     }
 }
@@ -321,7 +630,7 @@ impl Display for Tensor {
             }
 
             if y < self.shape.0 - 1 {
-                writeln!(f, "")?;
+                writeln!(f)?;
             }
         }
 
@@ -0,0 +1,418 @@
+//! Dense linear algebra building blocks.
+
+use super::{Shape, Tensor, C, R};
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinalgError {
+    NotSquare(Shape),
+    Singular,
+    // An iterative method such as the Denman–Beavers square root failed
+    NoConvergence,
+}
+
+impl Display for LinalgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotSquare(shape) => write!(f, "expected a square matrix, got shape {:?}", shape),
+            Self::Singular => write!(f, "matrix is singular"),
+            Self::NoConvergence => write!(f, "iteration did not converge"),
+        }
+    }
+}
+
+impl std::error::Error for LinalgError {}
+
+// LU decomposition with partial pivoting: P A = L U, with L and U packed in a
+// single matrix (the unit diagonal of L is implicit) and P stored as a row
+// permutation.
+pub(crate) struct Lu {
+    lu: Tensor,
+    perm: Vec<usize>,
+    // Sign of the permutation, (-1)^swaps
+    sign: R,
+}
+
+impl Lu {
+    // Returns None when the matrix is singular to working precision
+    pub(crate) fn new(a: &Tensor) -> Option<Lu> {
+        assert!(
+            a.shape.0 == a.shape.1,
+            "LU decomposition of non-square tensor"
+        );
+
+        let n = a.shape.0;
+        let mut lu = a.clone();
+        let mut perm: Vec<usize> = (0..n).collect();
+        let mut sign = 1.0;
+        let scale = a.data.iter().map(|c| c.norm()).fold(0.0, R::max);
+
+        for k in 0..n {
+            // Pick the largest pivot in the column
+            let pivot = (k..n)
+                .max_by(|&i, &j| lu[(i, k)].norm().total_cmp(&lu[(j, k)].norm()))
+                .unwrap();
+
+            if lu[(pivot, k)].norm() <= R::EPSILON * scale * n as R {
+                return None;
+            }
+
+            if pivot != k {
+                for j in 0..n {
+                    lu.data.swap(k * n + j, pivot * n + j);
+                }
+                perm.swap(k, pivot);
+                sign = -sign;
+            }
+
+            for i in k + 1..n {
+                let factor = lu[(i, k)] / lu[(k, k)];
+                lu[(i, k)] = factor;
+
+                for j in k + 1..n {
+                    let update = factor * lu[(k, j)];
+                    lu[(i, j)] -= update;
+                }
+            }
+        }
+
+        Some(Lu { lu, perm, sign })
+    }
+
+    // Product of the pivots, with the sign of the row permutation
+    pub(crate) fn det(&self) -> C {
+        (0..self.lu.shape.0).map(|i| self.lu[(i, i)]).product::<C>() * self.sign
+    }
+
+    // Solves A X = B for every column of B
+    pub(crate) fn solve(&self, b: &Tensor) -> Tensor {
+        let n = self.lu.shape.0;
+        assert!(
+            b.shape.0 == n,
+            "right hand side has the wrong number of rows"
+        );
+
+        let mut x = Tensor::new(
+            self.perm
+                .iter()
+                .flat_map(|&i| b.data[i * b.shape.1..(i + 1) * b.shape.1].iter().cloned())
+                .collect(),
+            b.shape,
+        );
+
+        for col in 0..b.shape.1 {
+            // Forward substitution with the unit lower triangle
+            for i in 0..n {
+                let mut sum = x[(i, col)];
+                for k in 0..i {
+                    sum -= self.lu[(i, k)] * x[(k, col)];
+                }
+                x[(i, col)] = sum;
+            }
+
+            // Back substitution with the upper triangle
+            for i in (0..n).rev() {
+                let mut sum = x[(i, col)];
+                for k in i + 1..n {
+                    sum -= self.lu[(i, k)] * x[(k, col)];
+                }
+                x[(i, col)] = sum / self.lu[(i, i)];
+            }
+        }
+
+        x
+    }
+}
+
+// Maximum absolute row sum
+pub(crate) fn norm_inf(a: &Tensor) -> R {
+    (0..a.shape.0)
+        .map(|i| (0..a.shape.1).map(|j| a[(i, j)].norm()).sum())
+        .fold(0.0, R::max)
+}
+
+// Relative tolerance used by the structural predicates and to group
+// degenerate eigenvalues
+pub(crate) const TOLERANCE: R = 1e-10;
+
+// Sweep limit for the Jacobi eigenvalue algorithm
+const MAX_SWEEPS: usize = 100;
+
+// Unitary 2x2 rotation [[u_pp, u_pq], [u_qp, u_qq]] such that U† M U is
+// diagonal, where M = [[app, apq], [conj(apq), aqq]] is Hermitian. The phase of
+// apq is removed first, leaving a real symmetric problem solved by a classic
+// Jacobi rotation.
+pub(crate) fn jacobi_rotation(app: R, aqq: R, apq: C) -> [C; 4] {
+    let r = apq.norm();
+    let phase = (apq / r).conj();
+    let theta = (aqq - app) / (2.0 * r);
+    let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+    let c = 1.0 / (t * t + 1.0).sqrt();
+    let s = t * c;
+
+    [C::new(c, 0.0), C::new(s, 0.0), phase * -s, phase * c]
+}
+
+// M <- M U, where U only mixes columns p and q
+pub(crate) fn rotate_columns(m: &mut Tensor, p: usize, q: usize, u: [C; 4]) {
+    for k in 0..m.shape.0 {
+        let (mp, mq) = (m[(k, p)], m[(k, q)]);
+        m[(k, p)] = mp * u[0] + mq * u[2];
+        m[(k, q)] = mp * u[1] + mq * u[3];
+    }
+}
+
+// M <- U† M, where U only mixes rows p and q
+fn rotate_rows(m: &mut Tensor, p: usize, q: usize, u: [C; 4]) {
+    for k in 0..m.shape.1 {
+        let (mp, mq) = (m[(p, k)], m[(q, k)]);
+        m[(p, k)] = u[0].conj() * mp + u[2].conj() * mq;
+        m[(q, k)] = u[1].conj() * mp + u[3].conj() * mq;
+    }
+}
+
+// Reorders the columns of a matrix
+pub(crate) fn permute_columns(m: &Tensor, order: &[usize]) -> Tensor {
+    let mut permuted = m.clone();
+
+    for (j, &k) in order.iter().enumerate() {
+        for i in 0..m.shape.0 {
+            permuted[(i, j)] = m[(i, k)];
+        }
+    }
+
+    permuted
+}
+
+impl Tensor {
+    pub fn trace(&self) -> C {
+        assert!(self.shape.0 == self.shape.1, "trace of non-square tensor");
+
+        (0..self.shape.0).map(|i| self[(i, i)]).sum()
+    }
+
+    // Determinant from the LU decomposition, zero for singular matrices
+    pub fn det(&self) -> Result<C, LinalgError> {
+        if self.shape.0 != self.shape.1 {
+            return Err(LinalgError::NotSquare(self.shape));
+        }
+
+        Ok(match Lu::new(self) {
+            Some(lu) => lu.det(),
+            None => C::new(0.0, 0.0),
+        })
+    }
+
+    // Inverse from the LU decomposition
+    pub fn inv(&self) -> Result<Tensor, LinalgError> {
+        if self.shape.0 != self.shape.1 {
+            return Err(LinalgError::NotSquare(self.shape));
+        }
+
+        match Lu::new(self) {
+            Some(lu) => Ok(lu.solve(&Tensor::eye(self.shape.0))),
+            None => Err(LinalgError::Singular),
+        }
+    }
+
+    // Numerical rank: the number of singular values above a tolerance
+    // relative to the largest one
+    pub fn rank(&self) -> usize {
+        let (_, s, _) = self.svd();
+        let largest = s.data.first().map(|c| c.re).unwrap_or(0.0);
+        let tolerance = R::EPSILON * self.shape.0.max(self.shape.1) as R * largest;
+
+        s.data.iter().filter(|c| c.re > tolerance).count()
+    }
+
+    pub fn is_hermitian(&self) -> bool {
+        self.shape.0 == self.shape.1
+            && (self - self.dag()).norm() <= TOLERANCE * self.norm().max(1.0)
+    }
+
+    pub fn is_unitary(&self) -> bool {
+        self.shape.0 == self.shape.1
+            && (&self.dag() * self - Tensor::eye(self.shape.0)).norm()
+                <= TOLERANCE * (self.shape.0 as R).sqrt()
+    }
+
+    pub fn is_positive_semidefinite(&self) -> bool {
+        if !self.is_hermitian() {
+            return false;
+        }
+
+        let (eigenvalues, _) = self.eigh();
+
+        eigenvalues
+            .data
+            .iter()
+            .all(|c| c.re >= -TOLERANCE * self.norm().max(1.0))
+    }
+
+    // Eigendecomposition of a Hermitian matrix with the cyclic Jacobi
+    // algorithm. Returns the eigenvalues in ascending order as a column vector
+    // and the matching orthonormal eigenvectors as the columns of a matrix, so
+    // that A = V diag(λ) V†.
+    pub fn eigh(&self) -> (Tensor, Tensor) {
+        assert!(self.is_hermitian(), "eigh on non-Hermitian tensor");
+
+        let n = self.shape.0;
+        let mut a = self.clone();
+        let mut v = Tensor::eye(n);
+        let threshold = R::EPSILON * self.norm();
+
+        for _ in 0..MAX_SWEEPS {
+            let off_diagonal: R = (0..n)
+                .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+                .map(|index| a[index].norm_sqr())
+                .sum();
+
+            if off_diagonal.sqrt() <= threshold {
+                break;
+            }
+
+            for p in 0..n {
+                for q in p + 1..n {
+                    if a[(p, q)].norm() <= threshold / n as R {
+                        continue;
+                    }
+
+                    let u = jacobi_rotation(a[(p, p)].re, a[(q, q)].re, a[(p, q)]);
+
+                    rotate_columns(&mut a, p, q, u);
+                    rotate_rows(&mut a, p, q, u);
+                    rotate_columns(&mut v, p, q, u);
+                }
+            }
+        }
+
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&i, &j| a[(i, i)].re.total_cmp(&a[(j, j)].re));
+
+        let eigenvalues = Tensor::new(
+            order.iter().map(|&i| C::new(a[(i, i)].re, 0.0)).collect(),
+            (n, 1),
+        );
+
+        (eigenvalues, permute_columns(&v, &order))
+    }
+
+    // Spectral decomposition of a Hermitian matrix: the distinct eigenvalues
+    // in ascending order, each with the projector onto its eigenspace, so that
+    // A = Σ λ P.
+    pub fn spectral(&self) -> Vec<(R, Tensor)> {
+        let (eigenvalues, eigenvectors) = self.eigh();
+        let n = self.shape.0;
+        let tolerance = TOLERANCE * self.norm().max(1.0);
+        let mut decomposition: Vec<(R, Tensor)> = Vec::new();
+
+        for k in 0..n {
+            let lambda = eigenvalues.data[k].re;
+            let projector = eigenvectors.column(k).proj();
+
+            match decomposition.last_mut() {
+                Some((last, sum)) if lambda - *last <= tolerance => {
+                    *sum += projector;
+                }
+                _ => decomposition.push((lambda, projector)),
+            }
+        }
+
+        decomposition
+    }
+
+    // Applies a function to the eigenvalues of a Hermitian matrix:
+    // f(A) = V diag(f(λ)) V†
+    pub fn spectral_map<F: Fn(R) -> C>(&self, f: F) -> Tensor {
+        let (eigenvalues, eigenvectors) = self.eigh();
+        let mut scaled = eigenvectors.clone();
+
+        for j in 0..self.shape.1 {
+            let value = f(eigenvalues.data[j].re);
+
+            for i in 0..self.shape.0 {
+                scaled[(i, j)] *= value;
+            }
+        }
+
+        &scaled * &eigenvectors.dag()
+    }
+
+    // Extracts a column as a column vector
+    pub fn column(&self, j: usize) -> Tensor {
+        Tensor::new(
+            (0..self.shape.0).map(|i| self[(i, j)]).collect(),
+            (self.shape.0, 1),
+        )
+    }
+}
+
+impl Tensor {
+    // Thin singular value decomposition A = U diag(s) V† with the one-sided
+    // Jacobi algorithm, which orthogonalises the columns of A with the same
+    // rotations eigh uses on their Gram matrix. For an m x n matrix with
+    // k = min(m, n) this returns U (m x k), the singular values in descending
+    // order as a k x 1 column vector, and V (n x k). Columns of U belonging to
+    // zero singular values are left as zero vectors.
+    pub fn svd(&self) -> (Tensor, Tensor, Tensor) {
+        let (m, n) = self.shape;
+
+        // Work on the conjugate transpose for wide matrices, so that there
+        // are never more columns than rows
+        if m < n {
+            let (u, s, v) = self.dag().svd();
+            return (v, s, u);
+        }
+
+        let mut a = self.clone();
+        let mut v = Tensor::eye(n);
+
+        for _ in 0..MAX_SWEEPS {
+            let mut rotated = false;
+
+            for p in 0..n {
+                for q in p + 1..n {
+                    let alpha = a.column(p).norm_sqr();
+                    let beta = a.column(q).norm_sqr();
+                    let gamma: C = (0..m).map(|k| a[(k, p)].conj() * a[(k, q)]).sum();
+
+                    if gamma.norm() <= R::EPSILON * (alpha * beta).sqrt() {
+                        continue;
+                    }
+
+                    let u = jacobi_rotation(alpha, beta, gamma);
+
+                    rotate_columns(&mut a, p, q, u);
+                    rotate_columns(&mut v, p, q, u);
+                    rotated = true;
+                }
+            }
+
+            if !rotated {
+                break;
+            }
+        }
+
+        let norms: Vec<R> = (0..n).map(|j| a.column(j).norm()).collect();
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&i, &j| norms[j].total_cmp(&norms[i]));
+
+        let mut u = permute_columns(&a, &order);
+        for (j, &k) in order.iter().enumerate() {
+            for i in 0..m {
+                u[(i, j)] = if norms[k] > 0.0 {
+                    u[(i, j)] / norms[k]
+                } else {
+                    C::new(0.0, 0.0)
+                };
+            }
+        }
+
+        let s = Tensor::new(
+            order.iter().map(|&k| C::new(norms[k], 0.0)).collect(),
+            (n, 1),
+        );
+
+        (u, s, permute_columns(&v, &order))
+    }
+}
@@ -0,0 +1,144 @@
+//! Matrix functions: exponential, square root and logarithm.
+
+use super::linalg::{norm_inf, LinalgError, Lu};
+use super::{Tensor, C, R};
+
+// Degree of the diagonal Padé approximant used by expm
+const PADE_DEGREE: usize = 6;
+
+// Iteration limit for the Denman–Beavers square root, and for the square
+// roots taken by the logarithm
+const MAX_ITERATIONS: usize = 100;
+
+impl Tensor {
+    // Matrix exponential, computed with a diagonal Padé approximant after
+    // scaling the matrix so that its norm is at most 1/2, then squaring the
+    // result back.
+    pub fn expm(&self) -> Tensor {
+        assert!(self.shape.0 == self.shape.1, "expm on non-square tensor");
+
+        let n = self.shape.0;
+        let norm = norm_inf(self);
+        let squarings = if norm > 0.5 {
+            (norm / 0.5).log2().ceil() as i32
+        } else {
+            0
+        };
+        let a = self / (2.0 as R).powi(squarings);
+
+        // N(A) = sum c_k A^k and D(A) = sum (-1)^k c_k A^k
+        let mut c = 0.5;
+        let mut x = a.clone();
+        let mut numerator = Tensor::eye(n) + &a * c;
+        let mut denominator = Tensor::eye(n) - &a * c;
+
+        for k in 2..=PADE_DEGREE {
+            c *= (PADE_DEGREE - k + 1) as R / (k * (2 * PADE_DEGREE - k + 1)) as R;
+            x = &a * &x;
+            numerator += &x * c;
+            if k % 2 == 0 {
+                denominator += &x * c;
+            } else {
+                denominator -= &x * c;
+            }
+        }
+
+        let mut result = match Lu::new(&denominator) {
+            Some(lu) => lu.solve(&numerator),
+            None => unreachable!("the Padé denominator is well conditioned after scaling"),
+        };
+
+        for _ in 0..squarings {
+            result = &result * &result;
+        }
+
+        result
+    }
+
+    // Principal matrix square root. Hermitian matrices go through their
+    // eigendecomposition, which also covers singular positive semidefinite
+    // ones such as pure state density matrices. Other matrices use the
+    // Denman–Beavers iteration, which fails on singular matrices and may not
+    // converge with eigenvalues on the negative real axis.
+    pub fn sqrtm(&self) -> Result<Tensor, LinalgError> {
+        if self.shape.0 != self.shape.1 {
+            return Err(LinalgError::NotSquare(self.shape));
+        }
+
+        if self.is_hermitian() {
+            return Ok(self.spectral_map(|lambda| C::new(lambda, 0.0).sqrt()));
+        }
+
+        let mut y = self.clone();
+        let mut z = Tensor::eye(self.shape.0);
+
+        for _ in 0..MAX_ITERATIONS {
+            let (y_inv, z_inv) = match (Lu::new(&y), Lu::new(&z)) {
+                (Some(y_lu), Some(z_lu)) => (
+                    y_lu.solve(&Tensor::eye(self.shape.0)),
+                    z_lu.solve(&Tensor::eye(self.shape.0)),
+                ),
+                _ => return Err(LinalgError::Singular),
+            };
+            let next = (&y + z_inv) * 0.5;
+            z = (z + y_inv) * 0.5;
+
+            let change = (&next - y).norm();
+            y = next;
+
+            if change <= 1e-14 * y.norm() {
+                return Ok(y);
+            }
+        }
+
+        Err(LinalgError::NoConvergence)
+    }
+
+    // Principal matrix logarithm. Hermitian matrices go through their
+    // eigendecomposition, other matrices use inverse scaling and squaring:
+    // square roots are taken until the matrix is close to the identity, where
+    // the Taylor series of log(I + X) converges quickly. Fails where the
+    // square root does.
+    pub fn logm(&self) -> Result<Tensor, LinalgError> {
+        if self.shape.0 != self.shape.1 {
+            return Err(LinalgError::NotSquare(self.shape));
+        }
+
+        if self.is_hermitian() {
+            return Ok(self.spectral_map(|lambda| C::new(lambda, 0.0).ln()));
+        }
+
+        let n = self.shape.0;
+        let mut a = self.clone();
+        let mut roots = 0;
+
+        while norm_inf(&(&a - Tensor::eye(n))) > 0.25 {
+            if roots == MAX_ITERATIONS {
+                return Err(LinalgError::NoConvergence);
+            }
+            a = a.sqrtm()?;
+            roots += 1;
+        }
+
+        let x = a - Tensor::eye(n);
+        let mut power = x.clone();
+        let mut result = x.clone();
+
+        for k in 2.. {
+            power = &power * &x;
+
+            let term = &power / k as R;
+            if term.norm() <= R::EPSILON * result.norm() {
+                break;
+            }
+
+            result = if k % 2 == 0 {
+                result - term
+            } else {
+                result + term
+            };
+        }
+
+        Ok(&result * (2.0 as R).powi(roots as i32))
+    }
+}
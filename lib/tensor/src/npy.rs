@@ -0,0 +1,217 @@
+//! NumPy `.npy` files of complex128 arrays and `.npz` bundles of them, for
+//! moving tensors to and from Python.
+//!
+//! Tensors are written in C order with their two dimensional shape. Reading
+//! also accepts one dimensional arrays, as kets, float64 arrays, and arrays in
+//! Fortran order.
+
+use super::{Shape, Tensor, C};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+
+// The magic string, version and header length come before the header, which
+// is padded for the data to start at a multiple of this alignment
+const ALIGNMENT: usize = 64;
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+impl Tensor {
+    pub fn write_npy(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_array(self, &mut writer)?;
+
+        writer.flush()
+    }
+
+    pub fn read_npy(path: impl AsRef<Path>) -> io::Result<Tensor> {
+        read_array(&mut BufReader::new(File::open(path)?))
+    }
+
+    // Writes named tensors to an uncompressed archive, as numpy.savez does,
+    // each one as the file `name.npy`
+    pub fn write_npz<'a, I>(path: impl AsRef<Path>, tensors: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (&'a str, &'a Tensor)>,
+    {
+        let mut archive = ZipWriter::new(BufWriter::new(File::create(path)?));
+        let options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .large_file(true);
+
+        for (name, tensor) in tensors {
+            archive.start_file(format!("{}.npy", name), options)?;
+            write_array(tensor, &mut archive)?;
+        }
+
+        archive.finish()?.flush()
+    }
+
+    // Reads the tensors of an archive written by numpy.savez or
+    // numpy.savez_compressed, in the order they are stored
+    pub fn read_npz(path: impl AsRef<Path>) -> io::Result<Vec<(String, Tensor)>> {
+        read_archive(BufReader::new(File::open(path)?))
+    }
+}
+
+fn read_archive<R: Read + Seek>(reader: R) -> io::Result<Vec<(String, Tensor)>> {
+    let mut archive = ZipArchive::new(reader)?;
+
+    (0..archive.len())
+        .map(|k| {
+            let mut file = archive.by_index(k)?;
+            let name = file.name()?;
+            let name = name.strip_suffix(".npy").unwrap_or(&name).to_string();
+
+            Ok((name, read_array(&mut file)?))
+        })
+        .collect()
+}
+
+fn write_array<W: Write>(tensor: &Tensor, writer: &mut W) -> io::Result<()> {
+    let mut header = format!(
+        "{{'descr': '<c16', 'fortran_order': False, 'shape': ({}, {}), }}",
+        tensor.shape.0, tensor.shape.1
+    );
+    let unpadded = MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(ALIGNMENT) - unpadded));
+    header.push('\n');
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+
+    for c in tensor.data.iter() {
+        writer.write_all(&c.re.to_le_bytes())?;
+        writer.write_all(&c.im.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn read_array<R: Read>(reader: &mut R) -> io::Result<Tensor> {
+    let mut preamble = [0; 8];
+    reader.read_exact(&mut preamble)?;
+
+    if &preamble[..6] != MAGIC {
+        return Err(invalid("not a .npy file"));
+    }
+
+    // Version 1 has a 2 byte header length, later versions a 4 byte one
+    let length = match preamble[6] {
+        1 => {
+            let mut length = [0; 2];
+            reader.read_exact(&mut length)?;
+            u16::from_le_bytes(length) as usize
+        }
+        2 | 3 => {
+            let mut length = [0; 4];
+            reader.read_exact(&mut length)?;
+            u32::from_le_bytes(length) as usize
+        }
+        version => return Err(invalid(format!("unsupported .npy version {}", version))),
+    };
+
+    let mut header = vec![0; length];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8(header).map_err(|_| invalid("header is not text"))?;
+    let (descr, fortran_order, shape) = parse_header(&header)?;
+
+    let size = match descr.as_str() {
+        "<c16" => 16,
+        "<f8" => 8,
+        descr => {
+            return Err(invalid(format!(
+                "unsupported dtype `{}`, expected complex128 or float64",
+                descr
+            )))
+        }
+    };
+
+    let length = shape
+        .0
+        .checked_mul(shape.1)
+        .and_then(|entries| entries.checked_mul(size))
+        .ok_or_else(|| invalid(format!("shape {:?} is too large", shape)))?;
+    // Read as the data comes rather than allocated up front, so that a header
+    // declaring a huge shape fails on the short file instead of on memory
+    let mut bytes = Vec::new();
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+
+    if bytes.len() != length {
+        return Err(invalid(format!(
+            "shape {:?} needs {} bytes of data, found {}",
+            shape,
+            length,
+            bytes.len()
+        )));
+    }
+
+    let float = |chunk: &[u8]| f64::from_le_bytes(chunk.try_into().unwrap());
+    let (m, n) = shape;
+    let data: Vec<C> = bytes
+        .chunks_exact(size)
+        .map(|entry| match size {
+            16 => C::new(float(&entry[..8]), float(&entry[8..])),
+            _ => C::new(float(entry), 0.0),
+        })
+        .collect();
+
+    match fortran_order {
+        // Entry (i, j) is at j m + i in column-major order
+        true => Ok(Tensor::new(
+            (0..m * n).map(|k| data[(k % n) * m + k / n]).collect(),
+            shape,
+        )),
+        false => Ok(Tensor::new(data, shape)),
+    }
+}
+
+// Reads the dtype, order and shape out of a header such as
+// {'descr': '<c16', 'fortran_order': False, 'shape': (4, 1), }
+fn parse_header(header: &str) -> io::Result<(String, bool, Shape)> {
+    let value = |key: &str| {
+        let start = header
+            .find(&format!("'{}':", key))
+            .ok_or_else(|| invalid(format!("header has no `{}`", key)))?;
+
+        Ok::<&str, io::Error>(header[start + key.len() + 3..].trim_start())
+    };
+
+    let descr = value("descr")?
+        .strip_prefix('\'')
+        .and_then(|rest| rest.split('\'').next())
+        .ok_or_else(|| invalid("malformed `descr`"))?;
+    let fortran_order = value("fortran_order")?.starts_with("True");
+
+    let shape = value("shape")?
+        .strip_prefix('(')
+        .and_then(|rest| rest.split(')').next())
+        .ok_or_else(|| invalid("malformed `shape`"))?
+        .split(',')
+        .map(str::trim)
+        .filter(|dimension| !dimension.is_empty())
+        .map(|dimension| dimension.parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| invalid("malformed `shape`"))?;
+
+    let shape = match shape[..] {
+        [] => (1, 1),
+        [n] => (n, 1),
+        [m, n] => (m, n),
+        _ => {
+            return Err(invalid(format!(
+                "expected at most 2 dimensions, got {}",
+                shape.len()
+            )))
+        }
+    };
+
+    Ok((descr.to_string(), fortran_order, shape))
+}
@@ -0,0 +1,130 @@
+//! Pauli operators and Pauli strings on qubit registers.
+
+use super::{Tensor, C};
+use std::ops::Mul;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Pauli {
+    I,
+    X,
+    Y,
+    Z,
+}
+
+impl Pauli {
+    pub fn from_char(c: char) -> Option<Pauli> {
+        match c {
+            'I' => Some(Self::I),
+            'X' => Some(Self::X),
+            'Y' => Some(Self::Y),
+            'Z' => Some(Self::Z),
+            _ => None,
+        }
+    }
+
+    pub fn matrix(&self) -> Tensor {
+        let (zero, one, i) = (C::new(0.0, 0.0), C::new(1.0, 0.0), C::new(0.0, 1.0));
+
+        match self {
+            Self::I => Tensor::eye(2),
+            Self::X => Tensor::new(vec![zero, one, one, zero], (2, 2)),
+            Self::Y => Tensor::new(vec![zero, -i, i, zero], (2, 2)),
+            Self::Z => Tensor::new(vec![one, zero, zero, -one], (2, 2)),
+        }
+    }
+
+    // Product of two Pauli operators as a phase and a Pauli operator, e.g.
+    // XY = iZ
+    pub fn product(self, rhs: Pauli) -> (C, Pauli) {
+        let i = C::new(0.0, 1.0);
+
+        match (self, rhs) {
+            (Self::I, p) | (p, Self::I) => (C::new(1.0, 0.0), p),
+            (p, q) if p == q => (C::new(1.0, 0.0), Self::I),
+            (Self::X, Self::Y) => (i, Self::Z),
+            (Self::Y, Self::Z) => (i, Self::X),
+            (Self::Z, Self::X) => (i, Self::Y),
+            (Self::Y, Self::X) => (-i, Self::Z),
+            (Self::Z, Self::Y) => (-i, Self::X),
+            (Self::X, Self::Z) => (-i, Self::Y),
+            _ => unreachable!("all pairs are covered"),
+        }
+    }
+}
+
+/// A phase times a tensor product of Pauli operators, one per qubit, with
+/// qubit 0 as the leftmost factor.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PauliString {
+    pub phase: C,
+    pub ops: Vec<Pauli>,
+}
+
+impl PauliString {
+    // Identity on n qubits
+    pub fn identity(n: usize) -> PauliString {
+        PauliString {
+            phase: C::new(1.0, 0.0),
+            ops: vec![Pauli::I; n],
+        }
+    }
+
+    // A single Pauli operator on qubit i of an n qubit register
+    pub fn single(n: usize, i: usize, op: Pauli) -> PauliString {
+        assert!(i < n, "qubit {} is outside of a {} qubit register", i, n);
+
+        let mut string = PauliString::identity(n);
+        string.ops[i] = op;
+        string
+    }
+
+    // Two Pauli strings commute when their operators differ (and neither is
+    // the identity) on an even number of qubits, and anticommute otherwise
+    pub fn commutes(&self, other: &PauliString) -> bool {
+        assert!(self.ops.len() == other.ops.len());
+
+        self.ops
+            .iter()
+            .zip(other.ops.iter())
+            .filter(|(p, q)| **p != Pauli::I && **q != Pauli::I && p != q)
+            .count()
+            % 2
+            == 0
+    }
+
+    pub fn to_tensor(&self) -> Tensor {
+        let sites: Vec<(usize, Tensor)> = self
+            .ops
+            .iter()
+            .enumerate()
+            .filter(|(_, op)| **op != Pauli::I)
+            .map(|(i, op)| (i, op.matrix()))
+            .collect();
+
+        &Tensor::expand_sites(self.ops.len(), &sites) * self.phase
+    }
+}
+
+impl Mul for &PauliString {
+    type Output = PauliString;
+
+    fn mul(self, rhs: &PauliString) -> PauliString {
+        assert!(self.ops.len() == rhs.ops.len());
+
+        let mut phase = self.phase * rhs.phase;
+        let ops = self
+            .ops
+            .iter()
+            .zip(rhs.ops.iter())
+            .map(|(p, q)| {
+                let (factor, op) = p.product(*q);
+                phase *= factor;
+                op
+            })
+            .collect();
+
+        PauliString { phase, ops }
+    }
+}
@@ -0,0 +1,54 @@
+//! Serialization of tensors, with the real and imaginary parts of the entries
+//! in separate arrays: `{"shape": [2, 1], "re": [1.0, 0.0], "im": [0.0, 0.0]}`.
+
+use super::{Shape, Tensor, C, R};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+struct Parts {
+    shape: Shape,
+    re: Vec<R>,
+    im: Vec<R>,
+}
+
+impl Serialize for Tensor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Parts {
+            shape: self.shape,
+            re: self.data.iter().map(|c| c.re).collect(),
+            im: self.data.iter().map(|c| c.im).collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Tensor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Tensor, D::Error> {
+        let Parts { shape, re, im } = Parts::deserialize(deserializer)?;
+        let entries = shape.0.checked_mul(shape.1).ok_or_else(|| {
+            de::Error::invalid_value(
+                de::Unexpected::Other(&format!("shape {}x{}", shape.0, shape.1)),
+                &"a shape whose number of entries fits in a usize",
+            )
+        })?;
+
+        if re.len() != entries || im.len() != entries {
+            return Err(de::Error::custom(format!(
+                "a tensor of shape {}x{} has {} entries, got {} real and {} imaginary parts",
+                shape.0,
+                shape.1,
+                entries,
+                re.len(),
+                im.len()
+            )));
+        }
+
+        Ok(Tensor::new(
+            re.into_iter()
+                .zip(im)
+                .map(|(re, im)| C::new(re, im))
+                .collect(),
+            shape,
+        ))
+    }
+}
@@ -0,0 +1,762 @@
+use super::Tensor;
+use num::complex::Complex64;
+
+const EPSILON: f64 = 1e-9;
+
+macro_rules! c {
+    ($re:expr, $im:expr) => {
+        Complex64::new($re, $im)
+    };
+    ($re:expr) => {
+        Complex64::new($re, 0.0)
+    };
+}
+
+fn matrix(rows: &[&[Complex64]]) -> Tensor {
+    Tensor::new(
+        rows.iter().flat_map(|row| row.iter().cloned()).collect(),
+        (rows.len(), rows[0].len()),
+    )
+}
+
+fn diagonal(values: &Tensor) -> Tensor {
+    let n = values.shape.0;
+    let mut d = Tensor::new(vec![c![0.0]; n * n], (n, n));
+
+    for i in 0..n {
+        d[(i, i)] = values.data[i];
+    }
+
+    d
+}
+
+#[test]
+fn eigh() {
+    let a = matrix(&[
+        &[c![2.0], c!(1.0, -1.0), c![0.5]],
+        &[c!(1.0, 1.0), c![3.0], c!(0.0, 2.0)],
+        &[c![0.5], c!(0.0, -2.0), c![-1.0]],
+    ]);
+    let (eigenvalues, eigenvectors) = a.eigh();
+
+    // Ascending, orthonormal and reconstructing the original matrix
+    assert!(eigenvalues.data.windows(2).all(|w| w[0].re <= w[1].re));
+    assert!(eigenvectors.is_unitary());
+    assert!(
+        (&(&eigenvectors * &diagonal(&eigenvalues)) * &eigenvectors.dag() - a.clone()).norm()
+            < EPSILON
+    );
+
+    // Trace is preserved
+    let trace: Complex64 = (0..3).map(|i| a[(i, i)]).sum();
+    assert!((eigenvalues.data.iter().sum::<Complex64>() - trace).norm() < EPSILON);
+}
+
+#[test]
+fn eigh_degenerate() {
+    let (eigenvalues, eigenvectors) = Tensor::eye(4).eigh();
+
+    assert!((eigenvalues - Tensor::new(vec![c![1.0]; 4], (4, 1))).norm() < EPSILON);
+    assert!((eigenvectors - Tensor::eye(4)).norm() < EPSILON);
+}
+
+#[test]
+fn predicates() {
+    let x = matrix(&[&[c![0.0], c![1.0]], &[c![1.0], c![0.0]]]);
+    let s = matrix(&[&[c![1.0], c![0.0]], &[c![0.0], c!(0.0, 1.0)]]);
+    let plus = Tensor::new(vec![c![1.0], c![1.0]], (2, 1)).unit();
+
+    assert!(x.is_hermitian());
+    assert!(x.is_unitary());
+    assert!(!x.is_positive_semidefinite());
+
+    assert!(!s.is_hermitian());
+    assert!(s.is_unitary());
+
+    assert!(plus.proj().is_positive_semidefinite());
+    assert!(!(&plus.proj() * 2.0).is_unitary());
+    assert!(!plus.is_hermitian());
+}
+
+#[test]
+fn spectral() {
+    let z = matrix(&[&[c![1.0], c![0.0]], &[c![0.0], c![-1.0]]]);
+    let p = Tensor::new(vec![c![1.0], c![0.0], c![0.0], c![0.0]], (4, 1)).proj();
+    let a = z.prod(&Tensor::eye(2)) + p;
+
+    // Eigenvalues are 2, 1 and -1 (twice)
+    let decomposition = a.spectral();
+    let values: Vec<f64> = decomposition.iter().map(|(lambda, _)| *lambda).collect();
+
+    assert_eq!(values.len(), 3);
+    assert!((values[0] + 1.0).abs() < EPSILON);
+    assert!((values[1] - 1.0).abs() < EPSILON);
+    assert!((values[2] - 2.0).abs() < EPSILON);
+    assert!(decomposition[0].1.is_positive_semidefinite());
+
+    let reconstructed = decomposition
+        .iter()
+        .map(|(lambda, projector)| projector * *lambda)
+        .reduce(|a, b| a + b)
+        .unwrap();
+    assert!((reconstructed - a.clone()).norm() < EPSILON);
+}
+
+#[test]
+fn matrix_functions() {
+    use super::LinalgError;
+
+    // Singular positive semidefinite matrices have a square root
+    let rho = Tensor::new(vec![c![1.0], c![1.0]], (2, 1)).unit().proj();
+    assert!((rho.sqrtm().unwrap() - rho.clone()).norm() < EPSILON);
+
+    let a = matrix(&[&[c![4.0], c!(1.0, 1.0)], &[c!(1.0, -1.0), c![3.0]]]);
+    let root = a.sqrtm().unwrap();
+    assert!((a.logm().unwrap().expm() - a.clone()).norm() < EPSILON);
+    assert!((&root * &root - a.clone()).norm() < EPSILON);
+
+    // Non-normal matrices use the iterative algorithms
+    let b = matrix(&[&[c![4.0], c![1.0]], &[c![0.0], c![9.0]]]);
+    let root = b.sqrtm().unwrap();
+    assert!((&root * &root - b.clone()).norm() < EPSILON);
+    assert!((b.logm().unwrap().expm() - b.clone()).norm() < EPSILON);
+
+    // which fail on singular matrices and on eigenvalues at -1
+    let nilpotent = matrix(&[&[c![0.0], c![1.0]], &[c![0.0], c![0.0]]]);
+    assert_eq!(nilpotent.sqrtm().unwrap_err(), LinalgError::Singular);
+    assert_eq!(nilpotent.logm().unwrap_err(), LinalgError::Singular);
+    let negative = matrix(&[&[c![-1.0], c![0.5]], &[c![0.0], c![-1.0]]]);
+    assert!(negative.logm().is_err());
+    assert_eq!(
+        Tensor::new(vec![c![1.0]; 2], (2, 1)).sqrtm().unwrap_err(),
+        LinalgError::NotSquare((2, 1))
+    );
+}
+
+#[test]
+fn svd() {
+    let tall = matrix(&[
+        &[c![1.0], c!(2.0, 1.0)],
+        &[c!(0.0, -1.0), c![0.5]],
+        &[c![3.0], c![-1.0]],
+    ]);
+
+    for a in [tall.clone(), tall.dag()] {
+        let (u, s, v) = a.svd();
+
+        assert_eq!(u.shape, (a.shape.0, 2));
+        assert_eq!(s.shape, (2, 1));
+        assert_eq!(v.shape, (a.shape.1, 2));
+        assert!(s.data[0].re >= s.data[1].re);
+        assert!((&u.dag() * &u - Tensor::eye(2)).norm() < EPSILON);
+        assert!((&v.dag() * &v - Tensor::eye(2)).norm() < EPSILON);
+        assert!((&(&u * &diagonal(&s)) * &v.dag() - a.clone()).norm() < EPSILON);
+    }
+
+    // Singular values are the square roots of the eigenvalues of A†A
+    let (eigenvalues, _) = (&tall.dag() * &tall).eigh();
+    let (_, s, _) = tall.svd();
+    assert!((s.data[0].re - eigenvalues.data[1].re.sqrt()).abs() < EPSILON);
+    assert!((s.data[1].re - eigenvalues.data[0].re.sqrt()).abs() < EPSILON);
+}
+
+#[test]
+fn schmidt() {
+    let zero = Tensor::new(vec![c![1.0], c![0.0]], (2, 1));
+    let one = Tensor::new(vec![c![0.0], c![1.0]], (2, 1));
+    let plus = (zero.clone() + one.clone()).unit();
+
+    // Product states have rank 1
+    let product = zero.prod(&plus).prod(&one);
+    let decomposition = product.schmidt(2, 4);
+    assert_eq!(decomposition.rank(), 1);
+    assert!((decomposition.coefficients[0] - 1.0).abs() < EPSILON);
+
+    // Bell states have two equal coefficients
+    let bell = (zero.prod(&zero) + &one.prod(&one) * c!(0.0, 1.0)).unit();
+    let decomposition = bell.schmidt(2, 2);
+    assert_eq!(decomposition.rank(), 2);
+    assert!((decomposition.coefficients[0] - 0.5f64.sqrt()).abs() < EPSILON);
+    assert!((decomposition.coefficients[1] - 0.5f64.sqrt()).abs() < EPSILON);
+
+    let reconstructed = (0..2)
+        .map(|k| &decomposition.a[k].prod(&decomposition.b[k]) * decomposition.coefficients[k])
+        .reduce(|a, b| a + b)
+        .unwrap();
+    assert!((reconstructed - bell).norm() < EPSILON);
+}
+
+fn qubit(amplitudes: [Complex64; 2]) -> Tensor {
+    Tensor::new(amplitudes.to_vec(), (2, 1))
+}
+
+fn bell() -> Tensor {
+    let zero = qubit([c![1.0], c![0.0]]);
+    let one = qubit([c![0.0], c![1.0]]);
+
+    (zero.prod(&zero) + one.prod(&one)).unit()
+}
+
+#[test]
+fn ptrace() {
+    let zero = qubit([c![1.0], c![0.0]]);
+    let plus = qubit([c![1.0], c![1.0]]).unit();
+    let state = zero.prod(&plus).prod(&zero);
+
+    assert!((state.ptrace(&[2, 2, 2], &[0, 2]) - plus.proj()).norm() < EPSILON);
+    assert!((state.ptrace(&[2, 2, 2], &[1]) - zero.prod(&zero).proj()).norm() < EPSILON);
+    assert!((state.ptrace(&[2, 4], &[0]) - plus.prod(&zero).proj()).norm() < EPSILON);
+    assert!((bell().ptrace(&[2, 2], &[1]) - &Tensor::eye(2) * 0.5).norm() < EPSILON);
+}
+
+#[test]
+fn entropy() {
+    let zero = qubit([c![1.0], c![0.0]]);
+    let mixed = &Tensor::eye(4) * 0.25;
+
+    assert!(zero.entropy().abs() < EPSILON);
+    assert!(bell().entropy().abs() < EPSILON);
+    assert!((bell().ptrace(&[2, 2], &[0]).entropy() - 1.0).abs() < EPSILON);
+    assert!((mixed.entropy() - 2.0).abs() < EPSILON);
+
+    for alpha in [0.5, 1.0, 2.0, 3.0] {
+        assert!((mixed.renyi_entropy(alpha) - 2.0).abs() < EPSILON);
+        assert!(zero.renyi_entropy(alpha).abs() < EPSILON);
+    }
+
+    assert!((bell().mutual_information(&[2, 2], &[0]) - 2.0).abs() < EPSILON);
+    assert!(zero.prod(&zero).mutual_information(&[2, 2], &[1]).abs() < EPSILON);
+}
+
+#[test]
+fn entanglement_measures() {
+    let zero = qubit([c![1.0], c![0.0]]);
+    let product = zero.prod(&qubit([c![1.0], c!(0.0, 1.0)]).unit());
+
+    assert!((bell().concurrence() - 1.0).abs() < EPSILON);
+    assert!(product.concurrence().abs() < EPSILON);
+    assert!((&Tensor::eye(4) * 0.25).concurrence().abs() < EPSILON);
+
+    assert!((bell().negativity(&[2, 2], &[1]) - 0.5).abs() < EPSILON);
+    assert!((bell().negativity(&[2, 2], &[0]) - 0.5).abs() < EPSILON);
+    assert!(product.negativity(&[2, 2], &[1]).abs() < EPSILON);
+}
+
+#[test]
+fn distances() {
+    let zero = qubit([c![1.0], c![0.0]]);
+    let one = qubit([c![0.0], c![1.0]]);
+    let plus = qubit([c![1.0], c![1.0]]).unit();
+    let mixed = &Tensor::eye(2) * 0.5;
+
+    assert!((zero.fidelity(&zero) - 1.0).abs() < EPSILON);
+    assert!(zero.fidelity(&one).abs() < EPSILON);
+    assert!((zero.fidelity(&plus) - 0.5).abs() < EPSILON);
+    assert!((zero.fidelity(&mixed) - 0.5).abs() < EPSILON);
+    assert!((mixed.fidelity(&zero) - 0.5).abs() < EPSILON);
+
+    assert!(zero.trace_distance(&zero).abs() < EPSILON);
+    assert!((zero.trace_distance(&one) - 1.0).abs() < EPSILON);
+    assert!((zero.trace_distance(&mixed) - 0.5).abs() < EPSILON);
+    assert!((zero.trace_distance(&plus) - 0.5f64.sqrt()).abs() < EPSILON);
+}
+
+#[test]
+fn partial_transpose() {
+    let zero = qubit([c![1.0], c![0.0]]);
+    let one = qubit([c![0.0], c![1.0]]);
+    let flip = one.prod(&zero) * zero.prod(&one).dag();
+
+    // Transposing either qubit of |10><01| gives |00><11| or |11><00|
+    let pt = flip.partial_transpose(&[2, 2], &[1]);
+    assert!((pt - one.prod(&one) * zero.prod(&zero).dag()).norm() < EPSILON);
+    let pt = flip.partial_transpose(&[2, 2], &[0]);
+    assert!((pt - zero.prod(&zero) * one.prod(&one).dag()).norm() < EPSILON);
+
+    // Transposing every subsystem is the full transpose
+    let a = Tensor::new((0..16).map(|k| c!(k as f64, 1.0)).collect(), (4, 4));
+    let transposed = a.partial_transpose(&[2, 2], &[0, 1]);
+    assert!((transposed - a.dag().map(|c| c.conj())).norm() < EPSILON);
+    assert!((a.partial_transpose(&[2, 2], &[]) - a.clone()).norm() < EPSILON);
+}
+
+#[test]
+#[should_panic(expected = "subsystem dimensions [2, 2] do not match a state of dimension 8")]
+fn partial_transpose_dimensions() {
+    Tensor::eye(8).partial_transpose(&[2, 2], &[0]);
+}
+
+#[test]
+#[should_panic(expected = "subsystem 2 is out of range for 2 subsystems")]
+fn partial_transpose_subsystem() {
+    bell().partial_transpose(&[2, 2], &[2]);
+}
+
+#[test]
+fn ppt() {
+    let zero = qubit([c![1.0], c![0.0]]);
+    let plus = qubit([c![1.0], c![1.0]]).unit();
+    let werner = |p: f64| &bell().density() * p + &Tensor::eye(4) * ((1.0 - p) / 4.0);
+
+    assert!(!bell().is_ppt(&[2, 2], &[1]));
+    assert!(zero.prod(&plus).is_ppt(&[2, 2], &[1]));
+    assert!(zero.prod(&bell()).is_ppt(&[2, 4], &[0]));
+    assert!(!zero.prod(&bell()).is_ppt(&[2, 2, 2], &[2]));
+
+    // Werner states are entangled for p > 1/3
+    assert!(werner(0.3).is_ppt(&[2, 2], &[1]));
+    assert!(!werner(0.4).is_ppt(&[2, 2], &[1]));
+}
+
+// Deterministic matrix with entries spread over the unit square
+fn filled(shape: (usize, usize), seed: usize) -> Tensor {
+    let data = (0..shape.0 * shape.1)
+        .map(|k| {
+            let x = ((k * 7919 + seed * 104729) % 1000) as f64 / 1000.0;
+            let y = ((k * 6271 + seed * 3571) % 997) as f64 / 997.0;
+
+            c!(x - 0.5, y - 0.5)
+        })
+        .collect();
+
+    Tensor::new(data, shape)
+}
+
+#[test]
+fn matrix_product() {
+    // Shapes crossing block boundaries, operators on kets and bras on
+    // operators, all matching the textbook triple loop exactly
+    for (m, n, p) in [(70, 130, 67), (100, 100, 1), (1, 100, 90), (3, 2, 5)] {
+        let (a, b) = (filled((m, n), 1), filled((n, p), 2));
+        let mut expected = Vec::new();
+
+        for i in 0..m {
+            for j in 0..p {
+                expected.push((0..n).map(|k| a[(i, k)] * b[(k, j)]).sum::<Complex64>());
+            }
+        }
+
+        let product = &a * &b;
+        assert_eq!(product.shape, (m, p));
+        assert_eq!(product.data, expected);
+    }
+}
+
+#[test]
+fn kronecker() {
+    use super::KroneckerProduct;
+
+    let a = matrix(&[
+        &[c![1.0], c!(0.0, 2.0), c![-1.0]],
+        &[c![0.5], c![3.0], c!(1.0, 1.0)],
+    ]);
+    let b = matrix(&[&[c![2.0], c!(0.0, -1.0)]]);
+    let d = matrix(&[&[c![1.0]], &[c![-2.0]], &[c!(0.5, 0.5)]]);
+
+    // Definition (A ⊗ B ⊗ D)[(i p + k, j q + l)] = A[(i, j)] (B ⊗ D)[(k, l)]
+    let product = [&a, &b, &d].prod();
+    let bd = b.prod(&d);
+    assert_eq!(product.shape, (6, 6));
+
+    for i in 0..2 {
+        for j in 0..3 {
+            for k in 0..3 {
+                for l in 0..2 {
+                    assert!(
+                        (product[(i * 3 + k, j * 2 + l)] - a[(i, j)] * bd[(k, l)]).norm() < EPSILON
+                    );
+                }
+            }
+        }
+    }
+
+    // Associative, and writing into a tensor of another shape
+    assert!((a.prod(&b).prod(&d) - product).norm() < EPSILON);
+
+    let mut out = Tensor::eye(8);
+    [d.clone(), a.clone()].prod_into(&mut out);
+    assert!((out - d.prod(&a)).norm() < EPSILON);
+}
+
+#[test]
+fn kronecker_operator() {
+    use super::{KronOp, KroneckerProduct};
+
+    let factors = vec![filled((2, 3), 1), filled((4, 2), 2), filled((3, 3), 3)];
+    let op = KronOp::new(factors.clone());
+    let dense = factors.prod();
+    assert_eq!(op.shape(), dense.shape);
+
+    // Applied factor by factor to kets, operators and bras
+    let (x, y) = (filled((18, 2), 4), filled((2, 24), 5));
+    assert!((&op * &x - &dense * &x).norm() < EPSILON);
+    assert!((&y * &op - &y * &dense).norm() < EPSILON);
+    assert!((op.dag().to_tensor() - dense.dag()).norm() < EPSILON);
+
+    // Products of paired factors stay factored
+    let other = KronOp::new(vec![
+        filled((3, 2), 6),
+        filled((2, 5), 7),
+        filled((3, 1), 8),
+    ]);
+    let product = op.compose(&other).unwrap();
+    assert_eq!(product.factors.len(), 3);
+    assert!((product.to_tensor() - &dense * &other.to_tensor()).norm() < EPSILON);
+    assert!(other.compose(&op).is_none());
+}
+
+#[test]
+fn arithmetic() {
+    let (a, b) = (filled((3, 3), 1), filled((3, 3), 2));
+    let s = Tensor::new(vec![c!(0.5, -2.0)], (1, 1));
+    let k = c!(1.5, 0.5);
+
+    // Owned, borrowed and in-place forms of each operation agree exactly
+    let sum = &a + &b;
+    assert_eq!((a.clone() + &b).data, sum.data);
+    assert_eq!((&a + b.clone()).data, sum.data);
+    assert_eq!((a.clone() + b.clone()).data, sum.data);
+
+    let difference = &a - &b;
+    assert_eq!((a.clone() - &b).data, difference.data);
+    assert_eq!((&a - b.clone()).data, difference.data);
+
+    let mut x = a.clone();
+    x += &b;
+    x -= b.clone();
+    x *= 2.0;
+    x /= k;
+    assert_eq!(x.data, (&(&(&a + &b) - &b) * 2.0 / k).data);
+
+    let product = &a * &b;
+    assert_eq!((a.clone() * &b).data, product.data);
+    assert_eq!((&a * b.clone()).data, product.data);
+    let mut x = a.clone();
+    x *= &b;
+    assert_eq!(x.data, product.data);
+
+    // Scalars on either side, as numbers or as 1x1 tensors
+    assert_eq!((k * &a).data, (&a * k).data);
+    assert_eq!((2.0 * a.clone()).data, (&a * 2.0).data);
+    assert_eq!((s.clone() * a.clone()).data, (&a * s.data[0]).data);
+    assert_eq!((a.clone() / s.clone()).data, (&a / s.data[0]).data);
+
+    assert_eq!((-a.clone()).data, (&a * -1.0).data);
+    assert_eq!((-&a).data, (&a * -1.0).data);
+    assert!((&(&a / &b) * &b - a).norm() < EPSILON);
+}
+
+#[test]
+fn pauli_strings() {
+    use super::{Pauli, PauliString};
+
+    let x0 = PauliString::single(2, 0, Pauli::X);
+    let y0 = PauliString::single(2, 0, Pauli::Y);
+    let z1 = PauliString::single(2, 1, Pauli::Z);
+    let x0y0 = &x0 * &y0;
+
+    assert_eq!(x0y0.ops, vec![Pauli::Z, Pauli::I]);
+    assert!((x0y0.phase - c!(0.0, 1.0)).norm() < EPSILON);
+    assert!(
+        (x0y0.to_tensor() - &x0.to_tensor() * &y0.to_tensor()).norm() < EPSILON,
+        "products of Pauli strings match matrix products"
+    );
+
+    assert!(x0.commutes(&z1));
+    assert!(!x0.commutes(&y0));
+    assert!((&x0 * &x0).to_tensor().is_unitary());
+
+    // XX and ZZ commute even though each pair of factors anticommutes
+    let xx = &x0 * &PauliString::single(2, 1, Pauli::X);
+    let zz = &PauliString::single(2, 0, Pauli::Z) * &z1;
+    assert!(xx.commutes(&zz));
+
+    let expanded = Tensor::expand_sites(3, &[(0, Pauli::X.matrix()), (2, Pauli::Z.matrix())]);
+    let string = &PauliString::single(3, 0, Pauli::X) * &PauliString::single(3, 2, Pauli::Z);
+    assert!((expanded - string.to_tensor()).norm() < EPSILON);
+}
+
+#[test]
+fn commutators() {
+    use super::Pauli;
+
+    let (x, y, z) = (Pauli::X.matrix(), Pauli::Y.matrix(), Pauli::Z.matrix());
+
+    assert!((x.commutator(&y) - &z * c!(0.0, 2.0)).norm() < EPSILON);
+    assert!((y.commutator(&z) - &x * c!(0.0, 2.0)).norm() < EPSILON);
+    assert!(x.anticommutator(&z).norm() < EPSILON);
+    assert!((y.anticommutator(&y) - &Tensor::eye(2) * 2.0).norm() < EPSILON);
+}
+
+#[test]
+fn determinant_and_inverse() {
+    use super::LinalgError;
+
+    let a = matrix(&[
+        &[c![2.0], c!(0.0, 1.0), c![0.0]],
+        &[c![1.0], c![3.0], c![-1.0]],
+        &[c![0.0], c![4.0], c!(1.0, 1.0)],
+    ]);
+
+    // Expanding along the first row
+    let expected = c![2.0] * (c![3.0] * c!(1.0, 1.0) + c![4.0])
+        - c!(0.0, 1.0) * (c![1.0] * c!(1.0, 1.0) - c![0.0]);
+    assert!((a.det().unwrap() - expected).norm() < EPSILON);
+    assert!((&a * &a.inv().unwrap() - Tensor::eye(3)).norm() < EPSILON);
+    assert!((&a.inv().unwrap() * &a - Tensor::eye(3)).norm() < EPSILON);
+
+    // Row swaps flip the sign of the determinant
+    let swap = matrix(&[&[c![0.0], c![1.0]], &[c![1.0], c![0.0]]]);
+    assert!((swap.det().unwrap() + c![1.0]).norm() < EPSILON);
+
+    let singular = matrix(&[&[c![1.0], c![2.0]], &[c![2.0], c![4.0]]]);
+    assert!(singular.det().unwrap().norm() < EPSILON);
+    assert_eq!(singular.inv().unwrap_err(), LinalgError::Singular);
+    assert_eq!(
+        Tensor::new(vec![c![1.0]; 2], (2, 1)).inv().unwrap_err(),
+        LinalgError::NotSquare((2, 1))
+    );
+
+    assert!((a.powi(-2) * a.powi(2) - Tensor::eye(3)).norm() < EPSILON);
+    assert!((swap.powi(i32::MIN) - Tensor::eye(2)).norm() < EPSILON);
+    assert!((swap.powu(u32::MAX) - swap.clone()).norm() < EPSILON);
+    assert!(((&a / &a) - Tensor::eye(3)).norm() < EPSILON);
+}
+
+#[test]
+fn trace_and_rank() {
+    let a = Tensor::new((0..9).map(|k| c!(k as f64, 0.0)).collect(), (3, 3));
+
+    assert!((a.trace() - c![12.0]).norm() < EPSILON);
+    assert_eq!(a.rank(), 2);
+    assert_eq!(Tensor::eye(4).rank(), 4);
+    assert_eq!(bell().proj().rank(), 1);
+    assert_eq!(Tensor::new(vec![c![0.0]; 6], (2, 3)).rank(), 0);
+}
+
+#[test]
+fn approximate_equality() {
+    let a = bell();
+    let nudged = &a * c![1.0 + 1e-7];
+
+    assert!(a.approx_eq(&nudged, 0.0, 1e-6));
+    assert!(!a.approx_eq(&nudged, 0.0, 1e-8));
+    assert!(a.approx_eq(&nudged, 1e-6, 0.0));
+    assert!(!a.approx_eq(&a.dag(), 1.0, 1.0));
+
+    let phase = c!(0.6, 0.8);
+    assert!((&a * phase).eq_up_to_global_phase(&a, 1e-12, 0.0));
+    assert!(!(&a * phase).approx_eq(&a, 1e-12, 0.0));
+    assert!(!qubit([c![1.0], c![0.0]]).eq_up_to_global_phase(
+        &qubit([c![0.0], c![1.0]]),
+        1e-12,
+        0.0
+    ));
+
+    crate::assert_state_eq!(a, nudged);
+    crate::assert_operator_eq!(a.proj(), nudged.proj(), 1e-6, 0.0);
+}
+
+#[test]
+fn canonical_phase() {
+    let a = &(bell() + qubit([c![0.0], c![1.0]]).prod(&qubit([c![1.0], c![0.0]]))) * c!(0.0, -1.0);
+    let canonical = a.canonical_phase();
+
+    assert!(canonical.eq_up_to_global_phase(&a, 1e-12, 0.0));
+    assert!((canonical.data[0] - c![a.data[0].norm()]).norm() < EPSILON);
+    assert!(canonical.approx_eq(&(&a * c!(0.6, 0.8)).canonical_phase(), 1e-12, 0.0));
+
+    // Leading zeros are skipped
+    let one = qubit([c![0.0], c!(0.0, 1.0)]);
+    assert!(one
+        .canonical_phase()
+        .approx_eq(&qubit([c![0.0], c![1.0]]), 1e-12, 0.0));
+    assert_eq!(
+        Tensor::new(vec![c![0.0]; 2], (2, 1))
+            .canonical_phase()
+            .norm(),
+        0.0
+    );
+}
+
+#[test]
+fn diff() {
+    let zero = qubit([c![1.0], c![0.0]]);
+    let diff = bell().diff(&zero.prod(&zero), 1e-8, 0.0);
+
+    assert!(diff.starts_with("2 of 4 entries differ"));
+    assert!(diff.contains("|00>") && diff.contains("|11>") && !diff.contains("|01>"));
+    assert!(Tensor::eye(4)
+        .diff(&bell().proj(), 1e-8, 0.0)
+        .contains("(1, 1)"));
+    assert!(zero.diff(&bell(), 1e-8, 0.0).contains("shapes differ"));
+}
+
+#[test]
+#[should_panic(expected = "failed for states")]
+fn assert_state_eq_panics() {
+    crate::assert_state_eq!(bell(), bell().proj().column(0));
+}
+
+// Path for a scratch file, unique to this test run
+#[cfg(feature = "npy")]
+fn scratch(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("tensor-{}-{}", std::process::id(), name))
+}
+
+// A .npy file as numpy writes it, with the given header dictionary
+#[cfg(feature = "npy")]
+fn npy_bytes(header: &str, data: &[f64]) -> Vec<u8> {
+    let header = format!("{:<1$}\n", header, 117);
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+
+    bytes.extend((header.len() as u16).to_le_bytes());
+    bytes.extend(header.as_bytes());
+    bytes.extend(data.iter().flat_map(|x| x.to_le_bytes()));
+    bytes
+}
+
+#[cfg(feature = "npy")]
+#[test]
+fn npy() {
+    let path = scratch("state.npy");
+    let tensor = filled((3, 5), 1);
+
+    tensor.write_npy(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(&bytes[..10], b"\x93NUMPY\x01\x00\x76\x00");
+    assert!(String::from_utf8_lossy(&bytes[10..128])
+        .starts_with("{'descr': '<c16', 'fortran_order': False, 'shape': (3, 5), }"));
+    assert_eq!(bytes.len(), 128 + 15 * 16);
+
+    let read = Tensor::read_npy(&path).unwrap();
+    assert_eq!(read.shape, (3, 5));
+    assert_eq!(read.data, tensor.data);
+
+    // Real arrays in Fortran order, and vectors, as numpy can write them
+    let fortran = npy_bytes(
+        "{'descr': '<f8', 'fortran_order': True, 'shape': (2, 3), }",
+        &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0],
+    );
+    std::fs::write(&path, fortran).unwrap();
+    let read = Tensor::read_npy(&path).unwrap();
+    assert_eq!(read.shape, (2, 3));
+    assert_eq!(read.data, [1.0, 2.0, 3.0, 4.0, 5.0, 6.0].map(|x| c![x]));
+
+    let vector = npy_bytes(
+        "{'descr': '<c16', 'fortran_order': False, 'shape': (2,), }",
+        &[0.5, 0.0, 0.0, -0.5],
+    );
+    std::fs::write(&path, vector).unwrap();
+    let read = Tensor::read_npy(&path).unwrap();
+    assert_eq!(read.shape, (2, 1));
+    assert_eq!(read.data, [c![0.5], c!(0.0, -0.5)]);
+
+    let integers = npy_bytes(
+        "{'descr': '<i8', 'fortran_order': False, 'shape': (1,), }",
+        &[0.0],
+    );
+    std::fs::write(&path, integers).unwrap();
+    let error = Tensor::read_npy(&path).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "unsupported dtype `<i8`, expected complex128 or float64"
+    );
+
+    // Shapes whose size overflows are rejected before allocating
+    let huge = npy_bytes(
+        "{'descr': '<c16', 'fortran_order': False, 'shape': (4294967296, 4294967296), }",
+        &[],
+    );
+    std::fs::write(&path, huge).unwrap();
+    let error = Tensor::read_npy(&path).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(
+        error.to_string(),
+        "shape (4294967296, 4294967296) is too large"
+    );
+
+    // and so are files shorter than their shape, without allocating for it
+    let short = npy_bytes(
+        "{'descr': '<c16', 'fortran_order': False, 'shape': (100000, 100000), }",
+        &[1.0, 0.0],
+    );
+    std::fs::write(&path, short).unwrap();
+    let error = Tensor::read_npy(&path).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(
+        error.to_string(),
+        "shape (100000, 100000) needs 160000000000 bytes of data, found 16"
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "npy")]
+#[test]
+fn npz() {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    let path = scratch("bundle.npz");
+    let (a, b) = (filled((4, 1), 1), filled((2, 2), 2));
+
+    Tensor::write_npz(&path, [("psi", &a), ("rho", &b)]).unwrap();
+    let tensors = Tensor::read_npz(&path).unwrap();
+    assert_eq!(tensors.len(), 2);
+    assert_eq!((tensors[0].0.as_str(), tensors[0].1.shape), ("psi", (4, 1)));
+    assert_eq!(tensors[0].1.data, a.data);
+    assert_eq!((tensors[1].0.as_str(), tensors[1].1.shape), ("rho", (2, 2)));
+    assert_eq!(tensors[1].1.data, b.data);
+
+    // Compressed archives, as written by numpy.savez_compressed
+    let mut archive = ZipWriter::new(std::fs::File::create(&path).unwrap());
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    archive.start_file("arr_0.npy", options).unwrap();
+    archive
+        .write_all(&npy_bytes(
+            "{'descr': '<f8', 'fortran_order': False, 'shape': (), }",
+            &[2.5],
+        ))
+        .unwrap();
+    archive.finish().unwrap();
+
+    let tensors = Tensor::read_npz(&path).unwrap();
+    assert_eq!(tensors[0].0, "arr_0");
+    assert_eq!(tensors[0].1.data, [c![2.5]]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialization() {
+    use super::{Pauli, PauliString};
+
+    let tensor = matrix(&[&[c![1.0], c!(0.0, -0.5)], &[c!(2.0, 1.0), c![0.0]]]);
+    let json = serde_json::to_string(&tensor).unwrap();
+    assert_eq!(
+        json,
+        r#"{"shape":[2,2],"re":[1.0,0.0,2.0,0.0],"im":[0.0,-0.5,1.0,0.0]}"#
+    );
+
+    let decoded: Tensor = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.shape, tensor.shape);
+    assert_eq!(decoded.data, tensor.data);
+
+    let error = serde_json::from_str::<Tensor>(r#"{"shape":[2,1],"re":[1.0],"im":[0.0]}"#);
+    assert!(error
+        .unwrap_err()
+        .to_string()
+        .contains("a tensor of shape 2x1 has 2 entries, got 1 real and 1 imaginary parts"));
+
+    let huge = r#"{"shape":[4294967296,4294967296],"re":[],"im":[]}"#;
+    assert!(serde_json::from_str::<Tensor>(huge)
+        .unwrap_err()
+        .to_string()
+        .starts_with("invalid value: shape 4294967296x4294967296, expected a shape whose"));
+
+    let string = PauliString::single(3, 1, Pauli::Y);
+    let json = serde_json::to_string(&string).unwrap();
+    assert_eq!(serde_json::from_str::<PauliString>(&json).unwrap(), string);
+}
@@ -1,7 +1,14 @@
-use dirac::xdirac as dirac;
+use dirac::{latex, qasm, quil, xdirac as dirac};
 use tensor::ToTensor;
 
 fn main() {
     dbg!(dirac!((|0> + |1>) / ||0> + |1>|));
     dbg!(dirac!(|+>));
+    dbg!(dirac!(|0> - i|1>, normalize));
+    dbg!(dirac!("(|0⟩ ⊗ |1⟩ + |1⟩ ⊗ |0⟩)/√2"));
+    dbg!(dirac!("θ = 1; Z_θ |01⟩"));
+
+    print!("{}", qasm!(CNOT (H x I) |00>));
+    print!("{}", quil!(CNOT (H x I) |00>));
+    println!("{}", latex!(1/sqrt(2) (|0> + |1>)));
 }